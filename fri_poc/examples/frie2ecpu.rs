@@ -1,4 +1,4 @@
-use icicle_babybear::field::ScalarField as Fr;
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
 use log::info;
 use merlin::Transcript;
 
@@ -16,8 +16,14 @@ fn main() {
         blow_up_factor: 4,
         folding_factor: 2,
         pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
         num_queries: 50,
         stopping_size: 256, //2^0
+        leaf_layout: LeafLayout::Single,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset: 1,
+        commit_config: CommitConfig::default(),
     };
     info!("Fri config: {:?}", fri_config);
     let starting_size: usize = 1 << 16;
@@ -37,11 +43,25 @@ fn main() {
     info!("Setup: {:?}", start.elapsed());
     let mut prover_transcript = Transcript::new(b"Real_FRI");
     let provertime = Instant::now();
-    let friproof: Friproof<Fr> = prove::<Fr>(fri_config, &mut prover_transcript, code_word.clone());
+    let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+        fri_config,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        code_word.clone(),
+    );
     info!("Prove: {:?}", provertime.elapsed());
     let verifiertime = Instant::now();
     let mut verifier_transcript = Transcript::new(b"Real_FRI");
-    verify(fri_config, friproof, &mut verifier_transcript).unwrap();
+    verify::<Fr, Ext>(
+        fri_config,
+        size.ilog2(),
+        b"my_Shitty_FRI",
+        b"public",
+        friproof,
+        &mut verifier_transcript,
+    )
+    .unwrap();
     info!("Verify time {:?}", verifiertime.elapsed());
     info!("Total time: {:?}", start.elapsed());
 }
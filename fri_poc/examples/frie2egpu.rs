@@ -1,8 +1,8 @@
-use icicle_babybear::field::ScalarField as Fr;
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
 use log::info;
 use merlin::Transcript;
 
-use fri_poc::prover::*;
+use fri_poc::device::DeviceFriProver;
 use fri_poc::utils::*;
 use fri_poc::{data_structures::*, verifier::verify};
 use std::time::Instant;
@@ -16,34 +16,46 @@ fn main() {
         blow_up_factor: 4,
         folding_factor: 2,
         pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
         num_queries: 50,
-        stopping_size: 1, //2^0
+        stopping_size: 16, //at least the blow up factor
+        leaf_layout: LeafLayout::Single,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset: 1,
+        commit_config: CommitConfig::default(),
     };
     info!("Fri config: {:?}", fri_config);
-    let starting_size: usize = 1 << 10;
+    let starting_size: usize = 1 << 14; //codeword of 2^16, above the old 2^12 segfault
     let input_data: Vec<Fr> = generate_random_vector::<Fr>(starting_size);
     let size: usize = input_data.len() * fri_config.blow_up_factor;
 
-    let is_coeff = true; //coeffs of a poly
-    let code_word: Vec<Fr> = if is_coeff {
-        //degree =2^k-1, i,e size = 2^k
-        //if input is in coeff form and codeword required is 2^k*blowup
-        coeff_to_eval_blowup::<Fr>(input_data.clone(), size)
-    } else {
-        //eval = 2^k and we need size = 2^k*blowup
-        eval_to_eval_blowup::<Fr>(input_data.clone(), size)
-    };
-    info!("Code word log size: {:?}", code_word.len().ilog2());
+    //the commit phase stays on the device, only the coefficients go up
+    let device_prover = DeviceFriProver::<Fr, Ext>::new(fri_config, size.ilog2());
+    info!("Code word log size: {:?}", size.ilog2());
     info!("Setup: {:?}", start.elapsed());
 
     let mut prover_transcript = Transcript::new(b"Real_FRI");
     let provertime = Instant::now();
-    let friproof: Friproof<Fr> = prove::<Fr>(fri_config, &mut prover_transcript, code_word.clone());
+    let friproof: Friproof<Ext> = device_prover.prove(
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        input_data,
+    );
     info!("Prove: {:?}", provertime.elapsed());
 
     let verifiertime = Instant::now();
     let mut verifier_transcript = Transcript::new(b"Real_FRI");
-    verify(fri_config, friproof, &mut verifier_transcript).unwrap();
+    verify::<Fr, Ext>(
+        fri_config,
+        size.ilog2(),
+        b"my_Shitty_FRI",
+        b"public",
+        friproof,
+        &mut verifier_transcript,
+    )
+    .unwrap();
     info!("Verify time {:?}", verifiertime.elapsed());
     info!("Total time: {:?}", start.elapsed());
 }
@@ -0,0 +1,395 @@
+use std::time::Instant;
+
+use icicle_core::{
+    bignum::BigNum, field::Field, ntt::{get_root_of_unity, NTTDomain, NTT}, ring::IntegerRing, traits::{Arithmetic, GenerateRandom}, vec_ops::VecOps
+};
+use log::{debug, info};
+use merlin::Transcript;
+
+use crate::{
+    data_structures::*, error::FriError, mmcs::{self, Matrix, MmcsOpening}, prover::FriProver, transcript::TranscriptProtocol, utils::*, verifier::{check_shape, FriVerifier}
+};
+
+// Batched FRI: many columns (codewords) in one proof, of the domain size or shorter.
+// The columns are committed in one mixed matrix commitment (`mmcs`), one matrix per column
+// length, leaf i of the tree is row i of the full length columns and row i mod h of the others,
+// so a query opens one leaf per coset element whatever the leaf layout.
+// After the root the transcript gives gamma, column i gets the coefficients
+//   (gamma^{2i} + gamma^{2i+1} x^{D_h-d_i})
+// with D_h = h/blow_up_factor for a column of length h and d_i its degree bound (number of
+// coefficients). The x^{D_h-d_i} term is the degree correction: it lifts every column to the
+// bound of its length, so a column above its own bound d_i pushes the combination above D_h.
+// FRI runs on the combination of the full length columns. The combination of the columns of
+// length h = size/k^r is rolled into layer r, the fold already has degree bound D_h there.
+// The combined first layer is never committed, the verifier recomputes it and the roll ins from
+// the opened rows.
+
+/// Proof for a batch of columns, see `prove_batch`.
+#[derive(Clone)]
+pub struct BatchFriproof<E> {
+    pub matrix_commitment: Vec<u8>, //mmcs root of the columns
+    pub matrix_opening: MmcsOpening, //folding_factor leaves per query, in query order
+    pub fri_proof: Friproof<E>, //layers of the combined codeword from the first fold on
+}
+
+impl<E: Arithmetic + Field + BigNum> BatchFriproof<E> {
+    /// Proof size in bytes: matrix root and rows, plus the FRI proof of the combination.
+    pub fn num_bytes(&self) -> usize {
+        self.matrix_commitment.len() + self.matrix_opening.num_bytes() + self.fri_proof.num_bytes()
+    }
+}
+
+// (gamma^{2i}, gamma^{2i+1}) for every column
+fn combination_coeffs<E: Arithmetic + Field + BigNum>(gamma: E, num_columns: usize) -> Vec<(E, E)> {
+    let mut current = E::one();
+    (0..num_columns)
+        .map(|_| {
+            let coeffs = (current, current * gamma);
+            current = current * gamma * gamma;
+            coeffs
+        })
+        .collect()
+}
+
+// value of the combination at x from the row of column values at x
+fn combine_row<F, E>(row: &[F], x: F, coeffs: &[(E, E)], shifts: &[u32]) -> E
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    row.iter()
+        .zip(coeffs.iter())
+        .zip(shifts.iter())
+        .fold(E::zero(), |acc, ((value, &(plain, shifted)), &shift)| {
+            let correction = plain + shifted * lift::<F, E>(&pow(x, shift));
+            acc + lift::<F, E>(value) * correction
+        })
+}
+
+/// Columns of one length: the FRI round r whose layer has that length, the column indices in
+/// input order, and their coefficients and degree shifts D_h - d_i.
+struct ColumnGroup<E> {
+    round: usize,
+    columns: Vec<usize>,
+    coeffs: Vec<(E, E)>,
+    shifts: Vec<u32>,
+}
+
+// columns grouped by length, longest first, the mmcs matrices in commit order. None when a
+// length is not size/k^r for a folded layer r < num_rounds, no column has the domain size,
+// or a degree bound is not in 1..=length/blow_up_factor
+fn column_groups<E: Arithmetic + Field + BigNum>(
+    fri_config: &Friconfig,
+    size: usize,
+    column_sizes: &[usize],
+    degree_bounds: &[usize],
+    gamma: E,
+) -> Option<Vec<ColumnGroup<E>>> {
+    let num_rounds = fri_config.num_rounds(size) as usize;
+    let log_k = fri_config.log_folding_factor() as usize;
+    let coeffs = combination_coeffs(gamma, column_sizes.len());
+    let mut groups: Vec<ColumnGroup<E>> = Vec::new();
+    for (column, (&column_size, &degree_bound)) in
+        column_sizes.iter().zip(degree_bounds.iter()).enumerate()
+    {
+        let round = (0..num_rounds).find(|&r| size >> (r * log_k) == column_size)?;
+        let max_degree = column_size / fri_config.blow_up_factor;
+        if degree_bound == 0 || degree_bound > max_degree {
+            return None;
+        }
+        let index = match groups.iter().position(|group| group.round == round) {
+            Some(index) => index,
+            None => {
+                groups.push(ColumnGroup {
+                    round,
+                    columns: Vec::new(),
+                    coeffs: Vec::new(),
+                    shifts: Vec::new(),
+                });
+                groups.len() - 1
+            }
+        };
+        groups[index].columns.push(column);
+        groups[index].coeffs.push(coeffs[column]);
+        groups[index].shifts.push((max_degree - degree_bound) as u32);
+    }
+    groups.sort_by_key(|group| group.round);
+    if groups.first().map(|group| group.round) != Some(0) {
+        return None;
+    }
+    Some(groups)
+}
+
+// combination of the rows of `matrix`, row j at offset*w_h^j with h its height
+fn combine_matrix<F, E>(matrix: &Matrix<F>, offset: F, group: &ColumnGroup<E>) -> Vec<E>
+where
+    F: Arithmetic + Field + BigNum + NTTDomain<F>,
+    E: Arithmetic + Field + BigNum,
+{
+    let rou: F = get_root_of_unity::<F>(matrix.height().try_into().unwrap()).unwrap();
+    let mut x: F = offset;
+    matrix
+        .values
+        .chunks(matrix.width)
+        .map(|row| {
+            let value = combine_row(row, x, &group.coeffs, &group.shifts);
+            x = x * rou;
+            value
+        })
+        .collect()
+}
+
+/// Prove that every column `code_words[i]` (evals with blow up included, of power of two
+/// lengths) is close to a polynomial with `degree_bounds[i]` coefficients, at most
+/// length/blow_up_factor. The longest columns set the domain size, the others have to be as long
+/// as a folded layer, size/k^r for a round r before the last one.
+/// One FRI proof for the random linear combination, see the comment at the top of this file.
+/// Zk mode is not supported, the columns are committed in the mmcs without mask or salt.
+pub fn prove_batch<F, E>(
+    fri_config: Friconfig,
+    domain_separator: &[u8],
+    public_input: &[u8],
+    transcript: &mut Transcript,
+    code_words: Vec<Vec<F>>,
+    degree_bounds: &[usize],
+) -> BatchFriproof<E>
+where
+    F: Arithmetic+Field+BigNum+VecOps<F> + NTTDomain<F> + NTT<F, F> + GenerateRandom,
+    E: Arithmetic+Field+BigNum+VecOps<E>,
+{
+    assert!(
+        !code_words.is_empty() && code_words.len() == degree_bounds.len(),
+        "one degree bound per column expected"
+    );
+    assert!(!fri_config.zk, "batch does not support zk mode");
+    assert!(
+        mmcs::is_supported(&fri_config.commit_config),
+        "batch commits the columns in an mmcs, which needs a binary tree without poseidon2"
+    );
+    let column_sizes: Vec<usize> = code_words.iter().map(|column| column.len()).collect();
+    let size: usize = *column_sizes.iter().max().unwrap();
+    assert!(
+        column_groups(&fri_config, size, &column_sizes, degree_bounds, E::one()).is_some(),
+        "column lengths should be folded layer lengths size/k^r and degree bounds in 1..=length/blow_up_factor"
+    );
+    let max_degree = size / fri_config.blow_up_factor;
+    let num_columns = code_words.len();
+
+    <Transcript as TranscriptProtocol<F>>::fri_domain_sep(
+        transcript,
+        domain_separator,
+        size.try_into().unwrap(),
+        public_input.to_vec(),
+    );
+    <Transcript as TranscriptProtocol<F>>::append_config(
+        transcript,
+        &fri_config,
+        max_degree.try_into().unwrap(),
+    );
+    <Transcript as TranscriptProtocol<F>>::append_columns(transcript, &column_sizes, degree_bounds);
+
+    let matrix_time = Instant::now();
+    //one row major matrix per column length, longest first
+    let mut grouped: Vec<(usize, Vec<Vec<F>>)> = Vec::new();
+    for (column, code_word) in code_words.into_iter().enumerate() {
+        let length = column_sizes[column];
+        match grouped.iter_mut().find(|(group_length, _)| *group_length == length) {
+            Some((_, columns)) => columns.push(code_word),
+            None => grouped.push((length, vec![code_word])),
+        }
+    }
+    grouped.sort_by_key(|(length, _)| std::cmp::Reverse(*length));
+    let matrices: Vec<Matrix<F>> = grouped
+        .iter()
+        .map(|(_, columns)| Matrix::from_columns(columns))
+        .collect();
+    drop(grouped);
+    let (matrix_commitment, prover_data) = mmcs::commit(&fri_config.commit_config, matrices);
+    <Transcript as TranscriptProtocol<F>>::append_commitment(
+        transcript,
+        b"matrix_commit",
+        &matrix_commitment,
+    );
+    let gamma: E = <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"gamma");
+    debug!("batch combination challenge {:?}", gamma);
+
+    //combined codeword on offset*H, and the roll in of every shorter length on its layer's coset
+    let groups = column_groups(&fri_config, size, &column_sizes, degree_bounds, gamma).unwrap();
+    let domain_offset: F = F::from_u32(fri_config.domain_offset);
+    let combined: Vec<E> = combine_matrix(&prover_data.matrices[0], domain_offset, &groups[0]);
+    let mut roll_ins: Vec<Vec<E>> = vec![Vec::new(); fri_config.num_rounds(size) as usize];
+    for (matrix, group) in prover_data.matrices.iter().zip(groups.iter()).skip(1) {
+        let layer_offset = pow(domain_offset, (size / matrix.height()) as u32);
+        roll_ins[group.round] = combine_matrix(matrix, layer_offset, group);
+    }
+    info!(
+        "prove_batch: commit and combine {:?} columns of {:?} lengths {:?}",
+        num_columns,
+        groups.len(),
+        matrix_time.elapsed()
+    );
+
+    let (fri_proof, query_indices) = FriProver::<F, E>::new(fri_config, size.ilog2())
+        .prove_layers(transcript, None, combined, &roll_ins);
+
+    //open the leaves of the first layer coset of every query
+    let coset_stride = size / fri_config.folding_factor;
+    let leaf_indices: Vec<usize> = query_indices
+        .iter()
+        .flat_map(|&index| (0..fri_config.folding_factor).map(move |j| index + j * coset_stride))
+        .collect();
+    let matrix_opening = prover_data.open(&leaf_indices);
+    BatchFriproof {
+        matrix_commitment,
+        matrix_opening,
+        fri_proof,
+    }
+}
+
+/// Verify a `prove_batch` proof for columns of lengths `column_sizes` with `degree_bounds`, the
+/// longest of length 2^log_domain_size. The rows are checked against the matrix root and combined
+/// at every query, the full length combination is the first layer of the FRI proof and the
+/// shorter ones are added to the folded values of their layer.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_batch<F, E>(
+    fri_config: Friconfig,
+    log_domain_size: u32,
+    column_sizes: &[usize],
+    degree_bounds: &[usize],
+    domain_separator: &[u8],
+    public_input: &[u8],
+    proof: BatchFriproof<E>,
+    transcript: &mut Transcript,
+) -> Result<(), FriError>
+where
+    F: Arithmetic + BigNum + Field + VecOps<F> + NTTDomain<F> + IntegerRing,
+    E: Arithmetic + BigNum + Field,
+{
+    let size = check_shape(&fri_config, log_domain_size, &proof.fri_proof, 1)?;
+    let max_degree = size / fri_config.blow_up_factor;
+    if !mmcs::is_supported(&fri_config.commit_config) {
+        return Err(FriError::MalformedProof("batch needs an mmcs commit config"));
+    }
+    if degree_bounds.is_empty()
+        || column_sizes.len() != degree_bounds.len()
+        || column_groups(&fri_config, size, column_sizes, degree_bounds, E::one()).is_none()
+    {
+        return Err(FriError::MalformedProof(
+            "column lengths should be folded layer lengths and degree bounds in 1..=length/blow_up_factor",
+        ));
+    }
+    let k = fri_config.folding_factor;
+    let leaves_per_layer = fri_config.num_queries * k;
+    if proof.matrix_opening.indices.len() != leaves_per_layer {
+        return Err(FriError::MalformedProof(
+            "matrix opening does not contain folding_factor leaves per query",
+        ));
+    }
+
+    <Transcript as TranscriptProtocol<F>>::fri_domain_sep(
+        transcript,
+        domain_separator,
+        size.try_into().unwrap(),
+        public_input.to_vec(),
+    );
+    <Transcript as TranscriptProtocol<F>>::append_config(
+        transcript,
+        &fri_config,
+        max_degree.try_into().unwrap(),
+    );
+    <Transcript as TranscriptProtocol<F>>::append_columns(transcript, column_sizes, degree_bounds);
+    <Transcript as TranscriptProtocol<F>>::append_commitment(
+        transcript,
+        b"matrix_commit",
+        &proof.matrix_commitment,
+    );
+    let gamma: E = <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"gamma");
+    debug!("batch combination challenge {:?}", gamma);
+
+    let groups = column_groups(&fri_config, size, column_sizes, degree_bounds, gamma).unwrap();
+    let base_element_size: usize = (F::one()).to_bytes_le().len();
+    let dims: Vec<(usize, usize)> = groups
+        .iter()
+        .map(|group| {
+            let height = size >> (group.round * fri_config.log_folding_factor() as usize);
+            (height, group.columns.len())
+        })
+        .collect();
+    proof
+        .matrix_opening
+        .authenticate(
+            &proof.matrix_commitment,
+            &fri_config.commit_config,
+            &dims,
+            base_element_size,
+        )
+        .map_err(|leaf| FriError::MerklePath { layer: 0, leaf })?;
+    let Some(rous) = dims
+        .iter()
+        .map(|&(height, _)| get_root_of_unity::<F>(height as u64).ok())
+        .collect::<Option<Vec<F>>>()
+    else {
+        return Err(FriError::MalformedProof("no root of unity for domain size"));
+    };
+    let domain_offset: F = F::from_u32(fri_config.domain_offset);
+    //row sizes were checked by the mmcs verification
+    let row = |slot: usize, group: usize| -> Vec<F> {
+        proof.matrix_opening.rows[slot][group]
+            .chunks(base_element_size)
+            .map(F::from_bytes_le)
+            .collect()
+    };
+    let coset_stride = size / k;
+    //the combination at the opened rows, leaves have to sit at index + j*n/k
+    let first_layer = |query_indices: &[usize]| -> Result<Vec<Vec<E>>, FriError> {
+        query_indices
+            .iter()
+            .enumerate()
+            .map(|(q, &index)| {
+                (0..k)
+                    .map(|j| {
+                        let slot = q * k + j;
+                        let expected = index + j * coset_stride;
+                        let found = proof.matrix_opening.indices[slot] as usize;
+                        if found != expected {
+                            return Err(FriError::QueryIndexMismatch {
+                                query: q,
+                                round: 0,
+                                expected,
+                                found,
+                            });
+                        }
+                        let x = domain_offset * pow(rous[0], expected as u32);
+                        Ok(combine_row(&row(slot, 0), x, &groups[0].coeffs, &groups[0].shifts))
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+    //the shorter columns at the query's position in their layer, every leaf of the coset opens
+    //the same row i mod h of them
+    let num_rounds = fri_config.num_rounds(size) as usize;
+    let roll_ins = |query_indices: &[usize]| -> Result<Vec<Vec<E>>, FriError> {
+        Ok(query_indices
+            .iter()
+            .enumerate()
+            .map(|(q, &index)| {
+                let mut values: Vec<E> = vec![E::zero(); num_rounds];
+                for (g, group) in groups.iter().enumerate().skip(1) {
+                    let height = dims[g].0;
+                    let position = index % height;
+                    let x = pow(domain_offset, (size / height) as u32) * pow(rous[g], position as u32);
+                    values[group.round] = combine_row(&row(q * k, g), x, &group.coeffs, &group.shifts);
+                }
+                values
+            })
+            .collect())
+    };
+    FriVerifier::<F>::new(fri_config, log_domain_size).verify_layers(
+        size,
+        &proof.fri_proof,
+        transcript,
+        Some(&first_layer),
+        Some(&roll_ins),
+    )
+}
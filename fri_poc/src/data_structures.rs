@@ -1,35 +1,272 @@
-use std::iter;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter,
+};
 
+use icicle_babybear::field::ScalarField as BabyBear;
 use icicle_core::{
-    bignum::BigNum, hash::Hasher, merkle::{MerkleProof, MerkleTree, MerkleTreeConfig}, ntt::{get_root_of_unity, NTTDomain}, polynomials::UnivariatePolynomial, ring::IntegerRing, traits::{Arithmetic, Invertible}, vec_ops::*
+    bignum::BigNum, hash::Hasher, merkle::{MerkleProof, MerkleTree, MerkleTreeConfig}, ntt::{get_root_of_unity, NTTDomain}, polynomials::UnivariatePolynomial, poseidon2::Poseidon2, ring::IntegerRing, traits::{Arithmetic, Invertible}, vec_ops::*
 };
 use icicle_core::field::Field;
-use icicle_hash::blake2s::Blake2s;
+use icicle_hash::{blake2s::Blake2s, blake3::Blake3, keccak::Keccak256};
 use icicle_runtime::memory::{HostOrDeviceSlice, HostSlice};
-use rand::distr::uniform::UniformSampler;
+use rand::{distr::uniform::UniformSampler, RngCore};
+
+/// How the elements of a layer are laid out in the merkle leaves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LeafLayout {
+    /// one field element per leaf, each coset element is opened with its own path
+    Single,
+    /// the folding_factor elements that fold together are one leaf, one path per fold step
+    Coset,
+}
+
+/// How the layers are folded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FriMode {
+    /// every layer is the fold of the previous one
+    Plain,
+    /// DEEP-FRI: after each fold the transcript gives an out of domain point z, the prover sends
+    /// y = g(z) of the fold g and the next layer is the quotient (g(X) - y)/(X - z)
+    Deep,
+}
+
+/// Hash used for the proof of work and the merkle trees, prover and verifier have to use the same one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HashKind {
+    Blake2s,
+    Blake3,
+    Keccak256,
+    /// one BabyBear permutation, the digest is one field element so keep pow_bits below ~24.
+    /// Not collision resistant as a merkle hash, only there to compare costs.
+    Poseidon2,
+}
+
+impl HashKind {
+    /// Hasher for inputs of `input_len` bytes, Poseidon2 has width input_len/4 BabyBear elements.
+    /// None if icicle has no such hasher (e.g. an unsupported Poseidon2 width).
+    pub fn hasher(&self, input_len: u64) -> Option<Hasher> {
+        match self {
+            HashKind::Blake2s => Blake2s::new(input_len).ok(),
+            HashKind::Blake3 => Blake3::new(input_len).ok(),
+            HashKind::Keccak256 => Keccak256::new(input_len).ok(),
+            HashKind::Poseidon2 => Poseidon2::new::<BabyBear>((input_len / 4) as u32, None).ok(),
+        }
+    }
+}
+
+/// Merkle tree committing the layers, prover and verifier build the same tree from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommitConfig {
+    pub leaf_hash: HashKind,
+    pub compression: HashKind,
+    pub arity: usize, //2, 4 or 8
+}
+
+/// binary Blake2s tree
+impl Default for CommitConfig {
+    fn default() -> Self {
+        CommitConfig {
+            leaf_hash: HashKind::Blake2s,
+            compression: HashKind::Blake2s,
+            arity: 2,
+        }
+    }
+}
+
+impl CommitConfig {
+    /// Poseidon2 only compresses field element digests, so it goes with Poseidon2 leafs.
+    pub fn is_valid(&self) -> bool {
+        matches!(self.arity, 2 | 4 | 8)
+            && (self.leaf_hash == HashKind::Poseidon2) == (self.compression == HashKind::Poseidon2)
+    }
+    /// Arity of every level from the leaves up. When log2(num_leaves) is not a multiple of
+    /// log2(arity) the bottom level takes the remainder, e.g. 2^5 leaves with arity 4 is [2, 4, 4].
+    pub fn level_arities(&self, num_leaves: usize) -> Vec<usize> {
+        let log_arity = self.arity.ilog2();
+        let tree_height = num_leaves.ilog2();
+        let remainder = tree_height % log_arity;
+        let mut arities: Vec<usize> = Vec::new();
+        if remainder != 0 {
+            arities.push(1 << remainder);
+        }
+        arities.extend(iter::repeat(self.arity).take((tree_height / log_arity) as usize));
+        arities
+    }
+    /// Leaf hasher followed by one compression per level, None for an unsupported hasher.
+    pub fn layer_hashers(&self, leaf_size: u64, num_leaves: usize) -> Option<Vec<Hasher>> {
+        let leaf_hasher = self.leaf_hash.hasher(leaf_size)?;
+        let digest_size = leaf_hasher.output_size();
+        let mut hashers: Vec<Hasher> = vec![leaf_hasher];
+        for arity in self.level_arities(num_leaves) {
+            let compress = self.compression.hasher(digest_size * arity as u64)?;
+            //every level has to output nodes of the same size
+            if compress.output_size() != digest_size {
+                return None;
+            }
+            hashers.push(compress);
+        }
+        Some(hashers)
+    }
+    /// Empty tree for `num_leaves` leafs of `leaf_size` bytes.
+    pub fn merkle_tree(&self, leaf_size: u64, num_leaves: usize) -> MerkleTree {
+        self.pruned_merkle_tree(leaf_size, num_leaves, 0)
+    }
+    /// Same tree, but only the levels from `store_min_layer` up are kept after `build`
+    /// (level 0 is the leaf digests). Proofs rehash the missing levels from the leaves.
+    pub fn pruned_merkle_tree(
+        &self,
+        leaf_size: u64,
+        num_leaves: usize,
+        store_min_layer: u64,
+    ) -> MerkleTree {
+        let hashers = self
+            .layer_hashers(leaf_size, num_leaves)
+            .expect("unsupported commit config for this leaf size");
+        let layer_hashes: Vec<&Hasher> = hashers.iter().collect();
+        MerkleTree::new(&layer_hashes, leaf_size, store_min_layer).unwrap()
+    }
+}
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Friconfig {
     pub blow_up_factor: usize,
     pub folding_factor: usize,
-    pub pow_bits: usize,
-    pub num_queries: usize,
+    pub pow_bits: usize, //at least this many leading zeros
+    pub pow_hash: HashKind,
+    pub num_queries: usize, //distinct query positions in the top layer, at most size/folding_factor
     pub stopping_size: usize, //should be power of two
-                              //pub (crate) Commitment_scheme: Merkle/MMCS,
+    pub leaf_layout: LeafLayout,
+    pub fri_mode: FriMode,
+    pub zk: bool, //mask the codeword with a random low degree polynomial and salt every leaf
+    pub domain_offset: u32, //1 for the subgroup H, otherwise the LDE and layers live on offset*H
+    pub commit_config: CommitConfig,
+}
+
+impl Friconfig {
+    /// log2 of the folding factor, folding factor should be a power of two
+    pub fn log_folding_factor(&self) -> u32 {
+        self.folding_factor.ilog2()
+    }
+    /// number of commit rounds to fold a codeword of `size` down to `stopping_size`
+    pub fn num_rounds(&self, size: usize) -> u32 {
+        (size.ilog2() - self.stopping_size.ilog2()) / self.log_folding_factor()
+    }
+    /// coefficients of the final poly, the last layer has degree < stopping_size/blow_up_factor
+    pub fn final_poly_len(&self) -> usize {
+        self.stopping_size / self.blow_up_factor
+    }
+    /// field elements hashed into one merkle leaf
+    pub fn elements_per_leaf(&self) -> usize {
+        match self.leaf_layout {
+            LeafLayout::Single => 1,
+            LeafLayout::Coset => self.folding_factor,
+        }
+    }
+    /// salt bytes at the end of every leaf, zero outside zk mode
+    pub fn salt_bytes(&self) -> usize {
+        if self.zk {
+            SALT_BYTES
+        } else {
+            0
+        }
+    }
+}
+
+/// Random bytes appended to every merkle leaf in zk mode.
+pub const SALT_BYTES: usize = 16;
+
+/// Merkle openings of a set of leaves of one layer against a single root, the layer
+/// commitment in `Friproof`, which is not repeated here. Leaves are listed in query order
+/// (folding_factor siblings per query), the authentication nodes are deduplicated: every
+/// digest that cannot be recomputed from the opened leaves is sent once, level by level, in
+/// increasing node index order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchOpening {
+    pub leaf_indices: Vec<u64>,
+    pub leaves: Vec<Vec<u8>>,
+    pub auth_nodes: Vec<Vec<u8>>,
+}
+
+impl BatchOpening {
+    /// Build the batch opening from full (non pruned) merkle proofs, `arities` of the levels
+    /// from the leaves up as in `CommitConfig::level_arities`. The path of a proof holds, for
+    /// every level, the arity-1 other digests hashed together with its node, in index order.
+    pub fn from_proofs(proofs: &[MerkleProof], arities: &[usize]) -> Self {
+        let mut opening = BatchOpening::default();
+        let Some(first) = proofs.first() else {
+            return opening;
+        };
+        let digest_size = first.get_root::<u8>().len();
+        for proof in proofs.iter() {
+            let (leaf, index) = proof.get_leaf::<u8>();
+            opening.leaf_indices.push(index);
+            opening.leaves.push(leaf.to_vec());
+        }
+        //known nodes of the current level, and a proof whose path goes through each of them
+        let mut known: BTreeMap<u64, &MerkleProof> = proofs
+            .iter()
+            .map(|proof| (proof.get_leaf::<u8>().1, proof))
+            .collect();
+        //bytes of the path used by the levels below
+        let mut path_offset: usize = 0;
+        for &arity in arities.iter() {
+            let arity = arity as u64;
+            let parents: BTreeSet<u64> = known.keys().map(|index| index / arity).collect();
+            for parent in parents {
+                let children = parent * arity..(parent + 1) * arity;
+                //the first known child has every other child of the parent in its path
+                let (&node, proof) = known.range(children.clone()).next().unwrap();
+                let path = proof.get_path::<u8>();
+                for child in children.filter(|child| !known.contains_key(child)) {
+                    let slot = (if child < node { child % arity } else { child % arity - 1 }) as usize;
+                    let start = path_offset + slot * digest_size;
+                    opening
+                        .auth_nodes
+                        .push(path[start..start + digest_size].to_vec());
+                }
+            }
+            path_offset += (arity as usize - 1) * digest_size;
+            known = known
+                .into_iter()
+                .map(|(index, proof)| (index / arity, proof))
+                .collect();
+        }
+        opening
+    }
+
+    /// Bytes sent for this opening, leaves and authentication nodes.
+    pub fn num_bytes(&self) -> usize {
+        self.leaf_indices.len() * std::mem::size_of::<u64>()
+            + self.leaves.iter().map(|leaf| leaf.len()).sum::<usize>()
+            + self.auth_nodes.iter().map(|node| node.len()).sum::<usize>()
+    }
 }
 
-//for future
-// pub struct commit_config {
-//     pub hasher: Hasher,
-//     pub hasher_dt: &'static [u8],
-//     pub compression: Hasher,
-//     pub compression_dt: &'static [u8],
-//     pub arity: usize,
-//     pub merkle_conf: MerkleTreeConfig,
-// }
+/// Bytes the same openings take as independent merkle proofs, each with its own path and root.
+pub fn individual_proofs_num_bytes(proofs: &[MerkleProof]) -> usize {
+    proofs
+        .iter()
+        .map(|proof| {
+            proof.get_root::<u8>().len()
+                + std::mem::size_of::<u64>()
+                + proof.get_leaf::<u8>().0.len()
+                + proof.get_path::<u8>().len()
+        })
+        .sum()
+}
+
+#[derive(Clone)]
 pub struct Friproof<T> {
-    pub query_proofs: Vec<Vec<MerkleProof>>, // contains path, root, leaf.
-    pub final_poly: Vec<T>,
+    pub layer_commitments: Vec<Vec<u8>>, // merkle root of every layer, what the challenges are drawn from
+    pub layer_openings: Vec<BatchOpening>, // one per layer for all queries, leafs and auth nodes against the layer commitment
+    pub final_poly: Vec<T>, //coefficients of the last layer, low degree first
+    pub deep_values: Vec<T>, //FriMode::Deep: value of every round's fold at its out of domain point
     pub pow_nonce: u64,
 }
 
@@ -42,33 +279,55 @@ impl<F: Arithmetic+Field+BigNum> Default for Friproof<F> {
 impl<F: Arithmetic+Field+BigNum> Friproof<F> {
     pub fn new() -> Self {
         Friproof {
-            query_proofs: Vec::<Vec<MerkleProof>>::new(),
+            layer_commitments: Vec::<Vec<u8>>::new(),
+            layer_openings: Vec::<BatchOpening>::new(),
             final_poly: Vec::<F>::new(),
+            deep_values: Vec::<F>::new(),
             pow_nonce: 0u64,
         }
     }
+    /// Proof size in bytes: layer commitments, merkle openings, final poly, deep values and pow nonce.
+    pub fn num_bytes(&self) -> usize {
+        self.layer_commitments
+            .iter()
+            .map(|commitment| commitment.len())
+            .sum::<usize>()
+            + self
+                .layer_openings
+                .iter()
+                .map(|opening| opening.num_bytes())
+                .sum::<usize>()
+            + (self.final_poly.len() + self.deep_values.len()) * F::one().to_bytes_le().len()
+            + std::mem::size_of::<u64>()
+    }
 }
 
-pub struct Frilayerdata<T> {
-    pub layer_code_words: Vec<Vec<T>>,
+// The first layer is the base field codeword, every layer after the first fold
+// lives in the extension field since the folding challenges are sampled there.
+pub struct Frilayerdata<F, E> {
+    pub base_code_word: Vec<F>,
+    pub layer_code_words: Vec<Vec<E>>, //extension layers, round 1 onwards
     pub layer_trees: Vec<MerkleTree>,
+    pub layer_salted_leaves: Vec<Vec<u8>>, //zk mode: leaf bytes with their salt, one per tree
 }
 
-impl<F: Arithmetic+Field+BigNum> Default for Frilayerdata<F> {
+impl<F: Arithmetic+Field+BigNum, E: Arithmetic+Field+BigNum> Default for Frilayerdata<F, E> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<F: Arithmetic+Field+BigNum> Frilayerdata<F> {
+impl<F: Arithmetic+Field+BigNum, E: Arithmetic+Field+BigNum> Frilayerdata<F, E> {
     pub fn new() -> Self {
         Frilayerdata {
-            layer_code_words: Vec::<Vec<F>>::new(),
+            base_code_word: Vec::<F>::new(),
+            layer_code_words: Vec::<Vec<E>>::new(),
             layer_trees: Vec::<MerkleTree>::new(),
+            layer_salted_leaves: Vec::<Vec<u8>>::new(),
         }
     }
     pub fn total_layers(&self) -> usize {
-        self.layer_code_words.len()
+        self.layer_trees.len()
     }
 }
 
@@ -78,7 +337,7 @@ pub struct Current_layer<T> {
 
 impl<F> Default for Current_layer<F>
 where
-    F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
+    F: Arithmetic + Field+BigNum+ VecOps<F>,
 {
     fn default() -> Self {
         Self::new()
@@ -89,20 +348,21 @@ impl<F> Current_layer<F>
 where
 F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
 {
-    pub fn new() -> Self {
-        Current_layer {
-            current_code_word: Vec::<F>::new(),
-        }
-    }
-    pub fn fold_evals_precompute_domain(
-        &mut self,
-        inv_domain: &mut Vec<F>,
-        &two_inv: &F,
-        alpha: F,
-    ) -> Vec<F> {
+    //this is real shit fold
+    //only for base field layers, the domain is read from the NTT domain of F
+    pub fn fold_evals(&mut self, coset_gen: F, alpha: F) -> Vec<F> {
         let len: usize = self.current_code_word.len();
+        let mut rou: F = get_root_of_unity::<F>(len.try_into().unwrap()).unwrap();
+        let mut rou_inv = rou.inv();
         let lenu64: u64 = len.try_into().unwrap();
-        let mut rou_inv_slice = HostSlice::from_mut_slice(&mut inv_domain[..]);
+        let gen = F::one();
+
+        //(1,w^{-1},w^{-2},...)
+        let mut rou_inv_vec: Vec<F> = iter::successors(Some(gen), |p| Some(*p * rou_inv))
+            .take(len / 2)
+            .collect();
+        //        println!("rou_inv_vec {:?}",rou_inv_vec.clone());
+        let mut rou_inv_slicebc = HostSlice::from_mut_slice(&mut rou_inv_vec[..]);
         let v_slice = HostSlice::from_mut_slice(&mut self.current_code_word[..]);
         //init arrays
         let mut v1 = vec![F::zero(); len / 2];
@@ -114,7 +374,6 @@ F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
         let mut res = vec![F::zero(); len / 2];
         let mut resf = vec![F::zero(); len / 2];
         let mut rouf = vec![F::zero(); len / 2];
-
         let mut odd_slice = HostSlice::from_mut_slice(&mut odd[..]);
         let mut even_slice = HostSlice::from_mut_slice(&mut even[..]);
         let mut odd1_slice = HostSlice::from_mut_slice(&mut odd1[..]);
@@ -124,7 +383,16 @@ F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
         let v1_slice = HostSlice::from_mut_slice(&mut v1[..]);
         let v2_slice = HostSlice::from_mut_slice(&mut v2[..]);
 
+        let mut rou_inv_slice = HostSlice::from_mut_slice(&mut rouf[..]);
         let cfg = VecOpsConfig::default();
+        // g^{-1}(1,w^{-1},w^{-2},...)
+        scalar_mul(
+            HostSlice::from_slice(&mut [coset_gen.inv()]),
+            rou_inv_slicebc,
+            rou_inv_slice,
+            &cfg,
+        )
+        .unwrap();
         //get odd and even slice
         let _ = slice(v_slice, 0, 1, lenu64, lenu64 / 2, &cfg, v1_slice);
         let _ = slice(v_slice, lenu64 / 2, 1, lenu64, lenu64 / 2, &cfg, v2_slice);
@@ -132,6 +400,8 @@ F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
         add_scalars(v1_slice, v2_slice, even_slice, &cfg).unwrap();
         //o=v1(x)-v2(-x)
         sub_scalars(v1_slice, v2_slice, odd_slice, &cfg).unwrap();
+        //o1= o* w^{-i}
+        /////
         let mut teven = vec![F::zero(); len / 2];
         let mut teven_slice = HostSlice::from_mut_slice(&mut teven[..]);
         let mut todd = vec![F::zero(); len / 2];
@@ -167,21 +437,26 @@ F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
         let res: Vec<F> = res_slice.as_slice().to_vec();
         res
     }
+}
 
-    //this is real shit fold
-    pub fn fold_evals(&mut self, coset_gen: F, alpha: F) -> Vec<F> {
+impl<F> Current_layer<F>
+where
+F: Arithmetic + Field+BigNum+ VecOps<F>,
+{
+    pub fn new() -> Self {
+        Current_layer {
+            current_code_word: Vec::<F>::new(),
+        }
+    }
+    pub fn fold_evals_precompute_domain(
+        &mut self,
+        inv_domain: &mut Vec<F>,
+        &two_inv: &F,
+        alpha: F,
+    ) -> Vec<F> {
         let len: usize = self.current_code_word.len();
-        let mut rou: F = get_root_of_unity::<F>(len.try_into().unwrap()).unwrap();
-        let mut rou_inv = rou.inv();
         let lenu64: u64 = len.try_into().unwrap();
-        let gen = F::one();
-
-        //(1,w^{-1},w^{-2},...)
-        let mut rou_inv_vec: Vec<F> = iter::successors(Some(gen), |p| Some(*p * rou_inv))
-            .take(len / 2)
-            .collect();
-        //        println!("rou_inv_vec {:?}",rou_inv_vec.clone());
-        let mut rou_inv_slicebc = HostSlice::from_mut_slice(&mut rou_inv_vec[..]);
+        let mut rou_inv_slice = HostSlice::from_mut_slice(&mut inv_domain[..]);
         let v_slice = HostSlice::from_mut_slice(&mut self.current_code_word[..]);
         //init arrays
         let mut v1 = vec![F::zero(); len / 2];
@@ -193,6 +468,7 @@ F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
         let mut res = vec![F::zero(); len / 2];
         let mut resf = vec![F::zero(); len / 2];
         let mut rouf = vec![F::zero(); len / 2];
+
         let mut odd_slice = HostSlice::from_mut_slice(&mut odd[..]);
         let mut even_slice = HostSlice::from_mut_slice(&mut even[..]);
         let mut odd1_slice = HostSlice::from_mut_slice(&mut odd1[..]);
@@ -202,16 +478,7 @@ F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
         let v1_slice = HostSlice::from_mut_slice(&mut v1[..]);
         let v2_slice = HostSlice::from_mut_slice(&mut v2[..]);
 
-        let mut rou_inv_slice = HostSlice::from_mut_slice(&mut rouf[..]);
         let cfg = VecOpsConfig::default();
-        // g^{-1}(1,w^{-1},w^{-2},...)
-        scalar_mul(
-            HostSlice::from_slice(&mut [coset_gen.inv()]),
-            rou_inv_slicebc,
-            rou_inv_slice,
-            &cfg,
-        )
-        .unwrap();
         //get odd and even slice
         let _ = slice(v_slice, 0, 1, lenu64, lenu64 / 2, &cfg, v1_slice);
         let _ = slice(v_slice, lenu64 / 2, 1, lenu64, lenu64 / 2, &cfg, v2_slice);
@@ -219,8 +486,6 @@ F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
         add_scalars(v1_slice, v2_slice, even_slice, &cfg).unwrap();
         //o=v1(x)-v2(-x)
         sub_scalars(v1_slice, v2_slice, odd_slice, &cfg).unwrap();
-        //o1= o* w^{-i}
-        /////
         let mut teven = vec![F::zero(); len / 2];
         let mut teven_slice = HostSlice::from_mut_slice(&mut teven[..]);
         let mut todd = vec![F::zero(); len / 2];
@@ -257,27 +522,173 @@ F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
         res
     }
 
+    /// One binary fold with the inverse twiddles (1, w^{-1}, .., w^{-(n/2-1)}) of the subgroup of
+    /// the code word length n: (v(x) + v(-x))/2 + alpha*(v(x) - v(-x))/2x. For a code word on the
+    /// coset g*H pass alpha*g^{-1}, the shift is a scalar so the twiddles do not depend on it.
+    pub fn fold_evals_twiddles(&self, inv_twiddles: &[F], two_inv: F, alpha: F) -> Vec<F> {
+        let half: usize = self.current_code_word.len() / 2;
+        let (v1, v2) = self.current_code_word.split_at(half);
+        let cfg = VecOpsConfig::default();
+        let mut even = vec![F::zero(); half];
+        let mut odd = vec![F::zero(); half];
+        let mut scratch = vec![F::zero(); half];
+        //e=v1(x)+v2(-x), o=(v1(x)-v2(-x))*x^{-1}
+        add_scalars(
+            HostSlice::from_slice(v1),
+            HostSlice::from_slice(v2),
+            HostSlice::from_mut_slice(&mut even[..]),
+            &cfg,
+        )
+        .unwrap();
+        sub_scalars(
+            HostSlice::from_slice(v1),
+            HostSlice::from_slice(v2),
+            HostSlice::from_mut_slice(&mut scratch[..]),
+            &cfg,
+        )
+        .unwrap();
+        mul_scalars(
+            HostSlice::from_slice(&scratch),
+            HostSlice::from_slice(&inv_twiddles[..half]),
+            HostSlice::from_mut_slice(&mut odd[..]),
+            &cfg,
+        )
+        .unwrap();
+        //res = e/2 + (alpha/2)*o
+        scalar_mul(
+            HostSlice::from_slice(&[two_inv * alpha]),
+            HostSlice::from_slice(&odd),
+            HostSlice::from_mut_slice(&mut scratch[..]),
+            &cfg,
+        )
+        .unwrap();
+        scalar_mul(
+            HostSlice::from_slice(&[two_inv]),
+            HostSlice::from_slice(&even),
+            HostSlice::from_mut_slice(&mut odd[..]),
+            &cfg,
+        )
+        .unwrap();
+        add_scalars(
+            HostSlice::from_slice(&odd),
+            HostSlice::from_slice(&scratch),
+            HostSlice::from_mut_slice(&mut even[..]),
+            &cfg,
+        )
+        .unwrap();
+        even
+    }
+
+    /// Fold by a power of two folding factor k in log2(k) binary folds with challenges
+    /// alpha, alpha^2, alpha^4,... and the inverse twiddles of every layer length precomputed,
+    /// inv_twiddles[log2(m)] for a layer of length m as in `inverse_twiddles`. The code word lives
+    /// on the coset g*H', shift_inv = g^{-1}, each binary fold squares the shift. Folds in place.
+    pub fn fold_with_twiddles(
+        &mut self,
+        inv_twiddles: &[Vec<F>],
+        two_inv: &F,
+        alpha: F,
+        shift_inv: F,
+        folding_factor: usize,
+    ) {
+        let mut alpha_pow = alpha;
+        let mut shift_inv_pow = shift_inv;
+        for _ in 0..folding_factor.ilog2() {
+            let log_len = self.current_code_word.len().ilog2() as usize;
+            self.current_code_word =
+                self.fold_evals_twiddles(&inv_twiddles[log_len], *two_inv, alpha_pow * shift_inv_pow);
+            alpha_pow = alpha_pow * alpha_pow;
+            shift_inv_pow = shift_inv_pow * shift_inv_pow;
+        }
+    }
+
+    /// Reorder the code word so the k elements that fold together, i + j*n/k, are contiguous:
+    /// leaf i holds (v[i], v[i+n/k], .., v[i+(k-1)n/k]) for i in 0..n/k.
+    pub fn coset_leaf_order(&self, folding_factor: usize) -> Vec<F> {
+        let coset_stride = self.current_code_word.len() / folding_factor;
+        (0..coset_stride)
+            .flat_map(|i| {
+                (0..folding_factor).map(move |j| self.current_code_word[i + j * coset_stride])
+            })
+            .collect()
+    }
+
+    /// Binary Blake2s tree with one element per leaf.
     pub fn commit(&mut self) -> MerkleTree {
-        //to replace this with generics and merkle config
-        let leaf_size: u64 = (F::one()).to_bytes_le().len().try_into().unwrap(); //4 for 32 bit fields
-        let no_of_leaves = self.current_code_word.len();
-        let hasher = Blake2s::new(leaf_size).unwrap();
-        let compress = Blake2s::new(hasher.output_size() * 2).unwrap();
-        let tree_height = no_of_leaves.ilog2() as usize;
-        let layer_hashes: Vec<&Hasher> = std::iter::once(&hasher)
-            .chain(std::iter::repeat(&compress).take(tree_height))
-            .collect();
+        self.commit_with_config(&CommitConfig::default(), 1)
+    }
+
+    /// Commit with `elements_per_leaf` consecutive elements of the code word hashed as one leaf,
+    /// use with `coset_leaf_order` to commit a whole coset in a leaf.
+    pub fn commit_with_config(
+        &mut self,
+        commit_config: &CommitConfig,
+        elements_per_leaf: usize,
+    ) -> MerkleTree {
+        self.commit_pruned(commit_config, elements_per_leaf, 0)
+    }
+
+    /// `commit_with_config` into a tree that keeps only the levels from `store_min_layer` up.
+    pub fn commit_pruned(
+        &mut self,
+        commit_config: &CommitConfig,
+        elements_per_leaf: usize,
+        store_min_layer: u64,
+    ) -> MerkleTree {
+        let element_size: usize = (F::one()).to_bytes_le().len(); //4 for 32 bit fields
+        let leaf_size: u64 = (element_size * elements_per_leaf).try_into().unwrap();
+        let no_of_leaves = self.current_code_word.len() / elements_per_leaf;
         let poly_slice: &mut [F] = self.current_code_word.as_mut_slice();
         let merkle_config = MerkleTreeConfig::default();
-        let merkle_tree = MerkleTree::new(&layer_hashes, leaf_size, 0).unwrap();
+        let merkle_tree = commit_config.pruned_merkle_tree(leaf_size, no_of_leaves, store_min_layer);
         merkle_tree
             .build(HostSlice::from_slice(poly_slice), &merkle_config)
             .unwrap();
         merkle_tree
     }
+    /// Commit with `salt_bytes` random bytes appended to every leaf of `elements_per_leaf`
+    /// elements, so the root and the authentication nodes say nothing about unopened leaves.
+    /// Returns the tree and the salted leaf bytes, which the openings are read from.
+    pub fn commit_with_salt(
+        &mut self,
+        commit_config: &CommitConfig,
+        elements_per_leaf: usize,
+        salt_bytes: usize,
+    ) -> (MerkleTree, Vec<u8>) {
+        let element_size: usize = (F::one()).to_bytes_le().len();
+        let leaf_size: usize = element_size * elements_per_leaf + salt_bytes;
+        let no_of_leaves = self.current_code_word.len() / elements_per_leaf;
+        let mut rng = rand::rng();
+        let mut salted_leaves: Vec<u8> = Vec::with_capacity(no_of_leaves * leaf_size);
+        for leaf in self.current_code_word.chunks(elements_per_leaf) {
+            for element in leaf.iter() {
+                salted_leaves.extend_from_slice(&element.to_bytes_le());
+            }
+            let mut salt: Vec<u8> = vec![0u8; salt_bytes];
+            rng.fill_bytes(&mut salt);
+            salted_leaves.extend_from_slice(&salt);
+        }
+        let merkle_config = MerkleTreeConfig::default();
+        let merkle_tree = commit_config.merkle_tree(leaf_size.try_into().unwrap(), no_of_leaves);
+        merkle_tree
+            .build(HostSlice::from_slice(&salted_leaves), &merkle_config)
+            .unwrap();
+        (merkle_tree, salted_leaves)
+    }
+    /// Proof of leaf `query_index` of a tree built by `commit_with_salt`.
+    pub fn salted_query(salted_leaves: &[u8], query_index: u64, layer_tree: &MerkleTree) -> MerkleProof {
+        let config = MerkleTreeConfig::default();
+        layer_tree
+            .get_proof(HostSlice::from_slice(salted_leaves), query_index, false, &config)
+            .unwrap()
+    }
     pub fn layer_query(&mut self, query_index: u64, layer_tree: &MerkleTree) -> MerkleProof {
+        Self::leaf_query(&self.current_code_word, query_index, layer_tree)
+    }
+    /// Proof of leaf `query_index` read straight from the committed `leaves`, no copy of the layer.
+    pub fn leaf_query(leaves: &[F], query_index: u64, layer_tree: &MerkleTree) -> MerkleProof {
         let config = MerkleTreeConfig::default();
-        let code_slice = HostSlice::<F>::from_slice(&self.current_code_word);
+        let code_slice = HostSlice::<F>::from_slice(leaves);
         layer_tree
             .get_proof(code_slice, query_index, false, &config)
             .unwrap()
@@ -285,14 +696,10 @@ F: Arithmetic + Field+BigNum+ VecOps<F> + NTTDomain<F>,
     ///for diagnostic: This uses the actual tree, so if it didnt pass
     /// it indicates an error in the commit part or merkle definition.
     pub fn test_verify_path(&mut self, layer_query_proof: MerkleProof) -> bool {
-        //to replace this with generics and merkle config
+        //the tree of `commit`
         let leaf_size: u64 = (F::one()).to_bytes_le().len().try_into().unwrap(); //4 for 32 bit fields
-        let hasher = Blake2s::new(leaf_size).unwrap();
-        let compress = Blake2s::new(hasher.output_size() * 2).unwrap();
-        let layer_hashes: Vec<&Hasher> = std::iter::once(&hasher)
-            .chain(std::iter::repeat(&compress).take(self.current_code_word.len().ilog2() as usize))
-            .collect();
-        let verifier_tree = MerkleTree::new(&layer_hashes, leaf_size, 0).unwrap();
+        let verifier_tree =
+            CommitConfig::default().merkle_tree(leaf_size, self.current_code_word.len());
         let result = verifier_tree.verify(&layer_query_proof);
         println!("result prover {:?}", result);
         result.unwrap()
@@ -0,0 +1,274 @@
+use std::{marker::PhantomData, time::Instant};
+
+use icicle_core::{
+    bignum::BigNum, field::Field, merkle::{MerkleProof, MerkleTree, MerkleTreeConfig}, ntt::{ntt, NTTConfig, NTTDir, NTTDomain, NTT}, traits::{Arithmetic, Invertible}, vec_ops::*
+};
+use icicle_runtime::memory::{DeviceSlice, DeviceVec, HostSlice};
+use log::{debug, info};
+use merlin::Transcript;
+
+use crate::{
+    data_structures::*, prover::{check_size, open_layer, sample_queries}, transcript::*, utils::*
+};
+
+// Commit phase with every layer resident on the device (on the CPU backend the "device" is host
+// memory managed by icicle, so the same code runs in the tests).
+// Only the coefficients go up, once as base field elements for the first layer commitment and
+// once lifted for the folds, both LDEs are device NTTs. Layers are folded and committed on the
+// device, what comes back is the layer roots, the stopping_size elements of the last layer and
+// the leaves and paths of the queried positions.
+// One binary fold is 5 vec ops with the halved twiddles cached on the device:
+//   fold(x) = v2 + (v1 - v2) * (1/2 + c*w^{-i}/2), c = alpha*shift^{-1}
+// Plain FRI without zk only, DEEP values and salted leaves are computed by `FriProver`.
+
+fn upload<T>(values: &[T]) -> DeviceVec<T> {
+    let mut device_values = DeviceVec::<T>::device_malloc(values.len()).unwrap();
+    device_values
+        .copy_from_host(HostSlice::from_slice(values))
+        .unwrap();
+    device_values
+}
+
+// leaf order of a layer: Single is the layer itself, Coset is the k x n/k transpose so that
+// leaf i holds (v[i], v[i+n/k], .., v[i+(k-1)n/k]) as in `Current_layer::coset_leaf_order`
+fn coset_leaves<T>(layer: &DeviceSlice<T>, folding_factor: usize) -> DeviceVec<T>
+where
+    T: Arithmetic + Field + BigNum + VecOps<T>,
+{
+    let mut leaves = DeviceVec::<T>::device_malloc(layer.len()).unwrap();
+    transpose_matrix(
+        layer,
+        folding_factor as u32,
+        (layer.len() / folding_factor) as u32,
+        &mut leaves[..],
+        &VecOpsConfig::default(),
+    )
+    .unwrap();
+    leaves
+}
+
+// merkle tree over device leaves, `elements_per_leaf` consecutive elements per leaf
+fn commit_leaves<T>(commit_config: &CommitConfig, leaves: &DeviceSlice<T>, elements_per_leaf: usize) -> MerkleTree
+where
+    T: Arithmetic + Field + BigNum,
+{
+    let element_size: usize = (T::one()).to_bytes_le().len();
+    let leaf_size: u64 = (element_size * elements_per_leaf).try_into().unwrap();
+    let merkle_tree = commit_config.merkle_tree(leaf_size, leaves.len() / elements_per_leaf);
+    merkle_tree
+        .build(leaves, &MerkleTreeConfig::default())
+        .unwrap();
+    merkle_tree
+}
+
+/// Prover keeping the commit phase on the device, for one `Friconfig` and codewords of up to
+/// 2^max_log_size elements. Built once like `FriProver`, the halved inverse twiddles of every
+/// layer length are uploaded in `new`.
+pub struct DeviceFriProver<F, E> {
+    pub fri_config: Friconfig,
+    pub max_log_size: u32,
+    half_inv_twiddles: Vec<DeviceVec<E>>, //[log n - 1] = (1, w_n^{-1}, .., w_n^{-(n/2-1)})/2
+    two_inv: E,
+    base_field: PhantomData<F>,
+}
+
+impl<F, E> DeviceFriProver<F, E>
+where
+    F: Arithmetic + Field + BigNum + VecOps<F> + NTTDomain<F> + NTT<F, F>,
+    E: Arithmetic + Field + BigNum + VecOps<E> + NTT<E, F>,
+{
+    pub fn new(fri_config: Friconfig, max_log_size: u32) -> Self {
+        assert!(
+            fri_config.fri_mode == FriMode::Plain && !fri_config.zk,
+            "the device prover runs plain FRI without zk"
+        );
+        assert!(max_log_size > 0, "max log size should be positive");
+        assert_ne!(fri_config.domain_offset, 0, "domain offset should be non zero");
+        let precompute_domain = Instant::now();
+        init_ntt_domain::<F>(1 << max_log_size);
+        let two_inv: E = E::from_u32(2).inv();
+        let half_inv_twiddles: Vec<DeviceVec<E>> = inverse_twiddles::<F>(max_log_size)[1..]
+            .iter()
+            .map(|twiddles| {
+                let halved: Vec<E> = twiddles
+                    .iter()
+                    .map(|twiddle| lift::<F, E>(twiddle) * two_inv)
+                    .collect();
+                upload(&halved)
+            })
+            .collect();
+        info!(
+            "DeviceFriProver: Precompute domain inverse {:?}",
+            precompute_domain.elapsed()
+        );
+        DeviceFriProver {
+            fri_config,
+            max_log_size,
+            half_inv_twiddles,
+            two_inv,
+            base_field: PhantomData,
+        }
+    }
+
+    // one binary fold of a layer of length n into a new buffer of length n/2
+    fn fold(&self, layer: &DeviceSlice<E>, alpha: E, shift_inv: E) -> DeviceVec<E> {
+        let half: usize = layer.len() / 2;
+        let cfg = VecOpsConfig::default();
+        let twiddles = &self.half_inv_twiddles[half.ilog2() as usize];
+        let mut weights = DeviceVec::<E>::device_malloc(half).unwrap();
+        let mut scratch = DeviceVec::<E>::device_malloc(half).unwrap();
+        let mut folded = DeviceVec::<E>::device_malloc(half).unwrap();
+        //weights = 1/2 + c*w^{-i}/2
+        scalar_mul(
+            HostSlice::from_slice(&[alpha * shift_inv]),
+            &twiddles[..],
+            &mut scratch[..],
+            &cfg,
+        )
+        .unwrap();
+        scalar_add(
+            HostSlice::from_slice(&[self.two_inv]),
+            &scratch[..],
+            &mut weights[..],
+            &cfg,
+        )
+        .unwrap();
+        //v2 + (v1 - v2)*weights
+        sub_scalars(&layer[..half], &layer[half..], &mut scratch[..], &cfg).unwrap();
+        mul_scalars(&scratch[..], &weights[..], &mut folded[..], &cfg).unwrap();
+        add_scalars(&layer[half..], &folded[..], &mut scratch[..], &cfg).unwrap();
+        scratch
+    }
+
+    // LDE of the zero padded coefficients on domain_offset*H, on the device
+    fn lde<T>(&self, coeffs: &[T], size: usize) -> DeviceVec<T>
+    where
+        T: Arithmetic + Field + BigNum + NTT<T, F>,
+    {
+        let mut padded: Vec<T> = coeffs.to_vec();
+        padded.resize(size, T::zero());
+        let device_coeffs = upload(&padded);
+        let mut ntt_cfg: NTTConfig<F> = NTTConfig::<F>::default();
+        ntt_cfg.coset_gen = F::from_u32(self.fri_config.domain_offset);
+        let mut evals = DeviceVec::<T>::device_malloc(size).unwrap();
+        ntt(&device_coeffs[..], NTTDir::kForward, &ntt_cfg, &mut evals[..]).unwrap();
+        evals
+    }
+
+    /// Same proof as `FriProver::prove` on the LDE of `coeffs` (size
+    /// next_power_of_two(coeffs.len())*blow_up_factor on domain_offset*H), with the codewords and
+    /// trees kept on the device.
+    pub fn prove(
+        &self,
+        domain_separator: &[u8],
+        public_input: &[u8],
+        transcript: &mut Transcript,
+        coeffs: Vec<F>,
+    ) -> Friproof<E> {
+        let fri_config = self.fri_config;
+        let size: usize = coeffs.len().next_power_of_two() * fri_config.blow_up_factor;
+        check_size(&fri_config, self.max_log_size, size);
+        debug!("device prover_size {:?}", size);
+        <Transcript as TranscriptProtocol<F>>::fri_domain_sep(
+            transcript,
+            domain_separator,
+            size.try_into().unwrap(),
+            public_input.to_vec(),
+        );
+        <Transcript as TranscriptProtocol<F>>::append_config(
+            transcript,
+            &fri_config,
+            (size / fri_config.blow_up_factor).try_into().unwrap(),
+        );
+
+        let commit_phase = Instant::now();
+        let k = fri_config.folding_factor;
+        let elements_per_leaf = fri_config.elements_per_leaf();
+        let num_rounds = fri_config.num_rounds(size);
+        //first layer committed in the base field, folded in the extension field
+        let base_layer: DeviceVec<F> = self.lde(&coeffs, size);
+        let base_leaves: DeviceVec<F> = match fri_config.leaf_layout {
+            LeafLayout::Single => base_layer,
+            LeafLayout::Coset => coset_leaves(&base_layer[..], k),
+        };
+        let mut layer: DeviceVec<E> = self.lde(&lift_vec::<F, E>(&coeffs), size);
+        //leaves of the extension layers, round 1 onwards, kept for the query phase
+        let mut layer_leaves: Vec<DeviceVec<E>> = Vec::new();
+        let mut layer_trees: Vec<MerkleTree> = Vec::new();
+        let mut friproof: Friproof<E> = Friproof::<E>::new();
+        let mut layer_offset_inv: E = lift::<F, E>(&F::from_u32(fri_config.domain_offset).inv());
+        for j in 0..num_rounds {
+            let leaves: Option<DeviceVec<E>> = match fri_config.leaf_layout {
+                LeafLayout::Coset if j > 0 => Some(coset_leaves(&layer[..], k)),
+                _ => None,
+            };
+            let tree = if j == 0 {
+                commit_leaves(&fri_config.commit_config, &base_leaves[..], elements_per_leaf)
+            } else {
+                let leaves_slice = leaves.as_ref().map_or(&layer[..], |leaves| &leaves[..]);
+                commit_leaves(&fri_config.commit_config, leaves_slice, elements_per_leaf)
+            };
+            //the root is the only thing that leaves the device in the commit phase
+            let current_root: Vec<u8> = tree.get_root::<u8>().unwrap().to_vec();
+            debug!("round: {:?}, Current_root: {:?}", j, current_root);
+            layer_trees.push(tree);
+            <Transcript as TranscriptProtocol<F>>::append_commitment(transcript, b"commit", &current_root);
+            friproof.layer_commitments.push(current_root);
+            let current_challenge: E =
+                <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"challenge");
+
+            let mut alpha_pow = current_challenge;
+            let mut shift_inv_pow = layer_offset_inv;
+            let mut folded: DeviceVec<E> = self.fold(&layer[..], alpha_pow, shift_inv_pow);
+            for _ in 1..fri_config.log_folding_factor() {
+                alpha_pow = alpha_pow * alpha_pow;
+                shift_inv_pow = shift_inv_pow * shift_inv_pow;
+                folded = self.fold(&folded[..], alpha_pow, shift_inv_pow);
+            }
+            let previous = std::mem::replace(&mut layer, folded);
+            if j > 0 {
+                layer_leaves.push(leaves.unwrap_or(previous));
+            }
+            layer_offset_inv = pow(layer_offset_inv, k as u32);
+        }
+        //last layer is stopping_size elements, interpolated on the host
+        let mut last_layer: Vec<E> = vec![E::zero(); layer.len()];
+        layer
+            .copy_to_host(HostSlice::from_mut_slice(&mut last_layer[..]))
+            .unwrap();
+        let mut final_coeffs: Vec<E> = interpolate_coset::<F, E>(&last_layer, layer_offset_inv);
+        final_coeffs.truncate(fri_config.final_poly_len());
+        friproof.final_poly = final_coeffs;
+        info!("device prove: Commit phase {:?}", commit_phase.elapsed());
+
+        //the trees and leaves stay on the device, so does the backend
+        let query_indices: Vec<usize> =
+            sample_queries::<F, E>(&fri_config, transcript, &mut friproof, size);
+
+        //query phase, the proofs copy the queried leaves and their paths to the host
+        let query_time = Instant::now();
+        let merkle_config = MerkleTreeConfig::default();
+        for (j, layer_tree) in layer_trees.iter().enumerate() {
+            let layer_size = size >> (j * fri_config.log_folding_factor() as usize);
+            let layer_proofs: Vec<MerkleProof> = open_layer(&fri_config, &query_indices, layer_size, |sibling| {
+                if j == 0 {
+                    layer_tree
+                        .get_proof(&base_leaves[..], sibling, false, &merkle_config)
+                        .unwrap()
+                } else {
+                    layer_tree
+                        .get_proof(&layer_leaves[j - 1][..], sibling, false, &merkle_config)
+                        .unwrap()
+                }
+            });
+            let arities = fri_config
+                .commit_config
+                .level_arities(layer_size / elements_per_leaf);
+            friproof
+                .layer_openings
+                .push(BatchOpening::from_proofs(&layer_proofs, &arities));
+        }
+        info!("device prove: query phase {:?}", query_time.elapsed());
+        friproof
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+
+/// Reasons a FRI proof is rejected by the verifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FriError {
+    /// Batch opening of a layer does not authenticate against its root, `leaf` is the first
+    /// leaf that fails (see `BatchOpening::authenticate`).
+    MerklePath { layer: usize, leaf: u64 },
+    /// The pow nonce does not give at least the required number of leading zeros.
+    ProofOfWork,
+    /// The opened position differs from the one the verifier derived from the sampled index.
+    QueryIndexMismatch {
+        query: usize,
+        round: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// Folding the opened coset does not match the opening in the next layer.
+    Collinearity { query: usize, round: usize },
+    /// Folding the last opened coset does not match the final poly.
+    FinalPoly { query: usize },
+    /// The final poly has more coefficients than the degree bound allows.
+    FinalPolyDegree { coeffs: usize, max_coeffs: usize },
+    /// The proof does not have the shape implied by the config.
+    MalformedProof(&'static str),
+}
+
+impl fmt::Display for FriError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FriError::MerklePath { layer, leaf } => {
+                write!(f, "bad merkle path at layer {}, leaf {}", layer, leaf)
+            }
+            FriError::ProofOfWork => write!(f, "nonce does not satisfy pow condition"),
+            FriError::QueryIndexMismatch {
+                query,
+                round,
+                expected,
+                found,
+            } => write!(
+                f,
+                "query {} opened index {} in round {}, expected {}",
+                query, found, round, expected
+            ),
+            FriError::Collinearity { query, round } => {
+                write!(f, "collinearity test failed at query {}, round {}", query, round)
+            }
+            FriError::FinalPoly { query } => {
+                write!(f, "collinearity test failed at final poly for query {}", query)
+            }
+            FriError::FinalPolyDegree { coeffs, max_coeffs } => write!(
+                f,
+                "final poly has {} coefficients, degree bound allows {}",
+                coeffs, max_coeffs
+            ),
+            FriError::MalformedProof(reason) => write!(f, "malformed proof: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FriError {}
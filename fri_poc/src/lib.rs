@@ -1,5 +1,13 @@
+pub mod batch;
 pub mod data_structures;
+pub mod device;
+pub mod error;
+pub mod mmcs;
+pub mod pcs;
+pub mod pow;
 pub mod prover;
+pub mod security;
+pub mod serialization;
 pub mod transcript;
 pub mod utils;
 pub mod verifier;
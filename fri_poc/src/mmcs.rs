@@ -0,0 +1,311 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use icicle_core::{
+    bignum::BigNum, field::Field, hash::{HashConfig, Hasher}, traits::Arithmetic
+};
+use icicle_runtime::memory::HostSlice;
+
+use crate::{
+    data_structures::{CommitConfig, HashKind}, utils::hash_bytes
+};
+
+// Mixed matrix commitment scheme: matrices of different (power of two) heights in one binary tree.
+// The tree has one leaf per row of the tallest matrices, leaf i hashes row i of every matrix of
+// that height. Codewords are in natural order, x_j and x_{j+h} = -x_j fold to position j of the
+// half size domain, so a level of width h compresses the children j and j+h. When there are
+// matrices of height h the node is compressed once more with the hash of their rows:
+//   node_j = C(C(child_j, child_{j+h}) || H(row j of every height h matrix))
+// so smaller matrices are injected at the level of their height.
+// Opening leaf i opens row i mod h of every matrix of height h, the folded position of i.
+// Binary trees only, the leaf hash takes rows of any length so Poseidon2 is not supported.
+
+/// Row major matrix, values[r*width + c] is row r column c.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Matrix<F> {
+    pub values: Vec<F>,
+    pub width: usize,
+}
+
+impl<F: Arithmetic + Field + BigNum> Matrix<F> {
+    pub fn new(values: Vec<F>, width: usize) -> Self {
+        assert!(
+            width > 0 && values.len() % width == 0,
+            "matrix values should be a multiple of the width"
+        );
+        Matrix { values, width }
+    }
+    /// Matrix with every codeword as a column, the codewords should have the same length.
+    pub fn from_columns(columns: &[Vec<F>]) -> Self {
+        let height = columns[0].len();
+        assert!(
+            columns.iter().all(|column| column.len() == height),
+            "columns should have the same length"
+        );
+        let values = (0..height)
+            .flat_map(|row| columns.iter().map(move |column| column[row]))
+            .collect();
+        Matrix::new(values, columns.len())
+    }
+    pub fn height(&self) -> usize {
+        self.values.len() / self.width
+    }
+    /// Little endian bytes of row `row`, what the leaf hash takes.
+    pub fn row_bytes(&self, row: usize) -> Vec<u8> {
+        self.values[row * self.width..(row + 1) * self.width]
+            .iter()
+            .flat_map(|value| value.to_bytes_le())
+            .collect()
+    }
+}
+
+/// Whether `commit_config` can be used for an MMCS tree: binary and no Poseidon2.
+pub fn is_supported(commit_config: &CommitConfig) -> bool {
+    commit_config.is_valid()
+        && commit_config.arity == 2
+        && commit_config.leaf_hash != HashKind::Poseidon2
+        && commit_config.compression != HashKind::Poseidon2
+}
+
+// digests of consecutive inputs of `input_len` bytes, in one hasher call
+fn hash_batch(hasher: &Hasher, input: &[u8], input_len: usize) -> Vec<Vec<u8>> {
+    let digest_size = hasher.output_size() as usize;
+    let mut output: Vec<u8> = vec![0u8; input.len() / input_len * digest_size];
+    hasher
+        .hash(
+            HostSlice::from_slice(input),
+            &HashConfig::default(),
+            HostSlice::from_mut_slice(&mut output),
+        )
+        .unwrap();
+    output.chunks(digest_size).map(|digest| digest.to_vec()).collect()
+}
+
+/// The matrices and every level of the tree, leaves first.
+pub struct MmcsProverData<F> {
+    pub matrices: Vec<Matrix<F>>,
+    pub levels: Vec<Vec<Vec<u8>>>,
+}
+
+/// Openings of a set of leaves (rows of the tallest matrices) against the MMCS root, with the
+/// matching row of every matrix. Authentication nodes are deduplicated level by level in
+/// increasing index order, as in `BatchOpening`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MmcsOpening {
+    pub indices: Vec<u64>,
+    pub rows: Vec<Vec<Vec<u8>>>, //per index, one row per matrix in commit order
+    pub auth_nodes: Vec<Vec<u8>>,
+}
+
+impl MmcsOpening {
+    /// Bytes sent for this opening, indices, rows and authentication nodes.
+    pub fn num_bytes(&self) -> usize {
+        self.indices.len() * std::mem::size_of::<u64>()
+            + self.rows.iter().flatten().map(|row| row.len()).sum::<usize>()
+            + self.auth_nodes.iter().map(|node| node.len()).sum::<usize>()
+    }
+}
+
+/// Commit to `matrices` of power of two heights in one tree, returns the root.
+pub fn commit<F>(commit_config: &CommitConfig, matrices: Vec<Matrix<F>>) -> (Vec<u8>, MmcsProverData<F>)
+where
+    F: Arithmetic + Field + BigNum,
+{
+    assert!(
+        is_supported(commit_config),
+        "mmcs needs a binary tree without poseidon2"
+    );
+    assert!(
+        !matrices.is_empty() && matrices.iter().all(|matrix| matrix.height().is_power_of_two()),
+        "mmcs needs matrices of power of two heights"
+    );
+    let max_height = matrices.iter().map(|matrix| matrix.height()).max().unwrap();
+    //hash of row i of every matrix of height `height`, for all rows
+    let row_digests = |height: usize| -> Option<Vec<Vec<u8>>> {
+        let injected: Vec<&Matrix<F>> =
+            matrices.iter().filter(|matrix| matrix.height() == height).collect();
+        if injected.is_empty() {
+            return None;
+        }
+        let input: Vec<u8> = (0..height)
+            .flat_map(|row| injected.iter().flat_map(move |matrix| matrix.row_bytes(row)))
+            .collect();
+        let input_len = input.len() / height;
+        let hasher = commit_config.leaf_hash.hasher(input_len as u64).unwrap();
+        Some(hash_batch(&hasher, &input, input_len))
+    };
+
+    let mut levels: Vec<Vec<Vec<u8>>> = vec![row_digests(max_height).unwrap()];
+    let digest_size = levels[0][0].len();
+    let compress = commit_config
+        .compression
+        .hasher(2 * digest_size as u64)
+        .unwrap();
+    assert_eq!(
+        compress.output_size() as usize,
+        digest_size,
+        "leaf hash and compression should have the same digest size"
+    );
+    let mut height = max_height;
+    while height > 1 {
+        height /= 2;
+        let children_level = levels.last().unwrap();
+        let children: Vec<u8> = (0..height)
+            .flat_map(|j| [children_level[j].as_slice(), children_level[j + height].as_slice()].concat())
+            .collect();
+        let mut nodes = hash_batch(&compress, &children, 2 * digest_size);
+        if let Some(injected) = row_digests(height) {
+            let input: Vec<u8> = nodes
+                .iter()
+                .zip(injected.iter())
+                .flat_map(|(node, rows)| [node.as_slice(), rows.as_slice()].concat())
+                .collect();
+            nodes = hash_batch(&compress, &input, 2 * digest_size);
+        }
+        levels.push(nodes);
+    }
+    let root = levels.last().unwrap()[0].clone();
+    (root, MmcsProverData { matrices, levels })
+}
+
+impl<F: Arithmetic + Field + BigNum> MmcsProverData<F> {
+    /// Open the leaves `indices` (rows of the tallest matrices) in one batched opening.
+    pub fn open(&self, indices: &[usize]) -> MmcsOpening {
+        let mut opening = MmcsOpening::default();
+        for &index in indices.iter() {
+            opening.indices.push(index as u64);
+            opening.rows.push(
+                self.matrices
+                    .iter()
+                    .map(|matrix| matrix.row_bytes(index % matrix.height()))
+                    .collect(),
+            );
+        }
+        let mut known: BTreeSet<usize> = indices.iter().cloned().collect();
+        for level in self.levels[..self.levels.len() - 1].iter() {
+            let half = level.len() / 2;
+            let parents: BTreeSet<usize> = known.iter().map(|index| index % half).collect();
+            for &parent in parents.iter() {
+                for child in [parent, parent + half] {
+                    if !known.contains(&child) {
+                        opening.auth_nodes.push(level[child].clone());
+                    }
+                }
+            }
+            known = parents;
+        }
+        opening
+    }
+}
+
+impl MmcsOpening {
+    /// Recompute the root from the opened rows of matrices with `dims` (height, width) in commit
+    /// order, `element_size` bytes per entry, and compare it with `root`. Leaves that share a row
+    /// of a smaller matrix have to carry the same row.
+    pub fn verify(
+        &self,
+        root: &[u8],
+        commit_config: &CommitConfig,
+        dims: &[(usize, usize)],
+        element_size: usize,
+    ) -> bool {
+        self.authenticate(root, commit_config, dims, element_size).is_ok()
+    }
+
+    /// `verify` with the index of the first leaf that fails, as in `BatchOpening::authenticate`.
+    pub fn authenticate(
+        &self,
+        root: &[u8],
+        commit_config: &CommitConfig,
+        dims: &[(usize, usize)],
+        element_size: usize,
+    ) -> Result<(), u64> {
+        let first_leaf: u64 = self.indices.first().copied().unwrap_or(0);
+        if !is_supported(commit_config)
+            || dims.is_empty()
+            || dims.iter().any(|&(height, width)| !height.is_power_of_two() || width == 0)
+            || self.indices.is_empty()
+            || self.rows.len() != self.indices.len()
+        {
+            return Err(first_leaf);
+        }
+        let max_height = dims.iter().map(|&(height, _)| height).max().unwrap();
+        for (&index, rows) in self.indices.iter().zip(self.rows.iter()) {
+            if index >= max_height as u64
+                || rows.len() != dims.len()
+                || rows
+                    .iter()
+                    .zip(dims.iter())
+                    .any(|(row, &(_, width))| row.len() != width * element_size)
+            {
+                return Err(index);
+            }
+        }
+        //hash of the opened rows of the height `height` matrices, per node of that level,
+        //or the leaf whose row differs from the one already opened at its node
+        let row_digests = |height: usize| -> Result<BTreeMap<u64, Vec<u8>>, u64> {
+            let injected: Vec<usize> = (0..dims.len()).filter(|&m| dims[m].0 == height).collect();
+            if injected.is_empty() {
+                return Ok(BTreeMap::new());
+            }
+            let mut digests: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+            for (&index, rows) in self.indices.iter().zip(self.rows.iter()) {
+                let input: Vec<u8> = injected.iter().flat_map(|&m| rows[m].clone()).collect();
+                let hasher = commit_config.leaf_hash.hasher(input.len() as u64).ok_or(index)?;
+                let digest = hash_bytes(&hasher, &input).ok_or(index)?;
+                let node = index % height as u64;
+                if digests.get(&node).is_some_and(|existing| *existing != digest) {
+                    return Err(index);
+                }
+                digests.insert(node, digest);
+            }
+            Ok(digests)
+        };
+
+        let mut known = row_digests(max_height)?;
+        let Some(digest_size) = known.values().next().map(|digest| digest.len()) else {
+            return Err(first_leaf);
+        };
+        let Some(compress) = commit_config.compression.hasher(2 * digest_size as u64) else {
+            return Err(first_leaf);
+        };
+        if compress.output_size() as usize != digest_size {
+            return Err(first_leaf);
+        }
+        let mut auth_nodes = self.auth_nodes.iter();
+        let mut height = max_height;
+        while height > 1 {
+            height /= 2;
+            let injected = row_digests(height)?;
+            let parents: BTreeSet<u64> = known.keys().map(|index| index % height as u64).collect();
+            let mut next: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+            for parent in parents {
+                let mut children: Vec<u8> = Vec::with_capacity(2 * digest_size);
+                for child in [parent, parent + height as u64] {
+                    match known.get(&child) {
+                        Some(digest) => children.extend_from_slice(digest),
+                        None => match auth_nodes.next() {
+                            Some(node) if node.len() == digest_size => children.extend_from_slice(node),
+                            _ => return Err(first_leaf),
+                        },
+                    }
+                }
+                let Some(mut node) = hash_bytes(&compress, &children) else {
+                    return Err(first_leaf);
+                };
+                if let Some(rows) = injected.get(&parent) {
+                    let Some(injected_node) = hash_bytes(&compress, &[node, rows.clone()].concat()) else {
+                        return Err(first_leaf);
+                    };
+                    node = injected_node;
+                }
+                next.insert(parent, node);
+            }
+            known = next;
+        }
+        if known.get(&0).is_some_and(|computed| computed.as_slice() == root) && auth_nodes.next().is_none() {
+            Ok(())
+        } else {
+            Err(first_leaf)
+        }
+    }
+}
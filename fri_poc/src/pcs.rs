@@ -0,0 +1,267 @@
+use icicle_core::{
+    bignum::BigNum, field::Field, merkle::{MerkleProof, MerkleTree}, ntt::{get_root_of_unity, NTTDomain, NTT}, ring::IntegerRing, traits::{Arithmetic, GenerateRandom, Invertible}, vec_ops::VecOps
+};
+use log::debug;
+use merlin::Transcript;
+
+use crate::{
+    data_structures::*, error::FriError, prover::FriProver, transcript::TranscriptProtocol, utils::*, verifier::{check_shape, FriVerifier}
+};
+
+// FRI as a univariate polynomial commitment.
+// commit: merkle root of the LDE of p on offset*H, one element per leaf in natural order.
+// open: the transcript gives an out of domain point z, y = p(z), and FRI runs on the DEEP
+// quotient q(X) = (p(X) - y)/(X - z), which is low degree iff p(z) = y.
+// q has one coefficient less than p, FRI has the bound of p. Degree correction: FRI runs on
+// q(X)*(1 + r*X) for a transcript challenge r, which is within the bound iff q is one below it,
+// so a p with one coefficient too many is rejected.
+// The quotient is not committed, the verifier opens p at the queried positions and recomputes
+// q(x) = (p(x) - y)/(x - z) as the first layer of the FRI proof.
+
+/// What the prover keeps after `commit` to open later.
+pub struct PcsProverData<F> {
+    pub coeffs: Vec<F>,
+    pub code_word: Current_layer<F>, //LDE of coeffs, in leaf order
+    pub tree: MerkleTree,
+}
+
+/// Opening of a committed polynomial at the transcript point `point`.
+#[derive(Clone)]
+pub struct PcsOpening<E> {
+    pub point: E, //z, drawn from the transcript after the commitment
+    pub value: E, //y = p(z)
+    pub codeword_opening: BatchOpening, //folding_factor evaluations of p per query, in query order
+    pub fri_proof: Friproof<E>, //layers of the quotient from the first fold on
+}
+
+impl<E: Arithmetic + Field + BigNum> PcsOpening<E> {
+    /// Proof size in bytes: point and value, evaluations of p and the quotient FRI proof.
+    pub fn num_bytes(&self) -> usize {
+        2 * E::one().to_bytes_le().len()
+            + self.codeword_opening.num_bytes()
+            + self.fri_proof.num_bytes()
+    }
+}
+
+// statement, commitment and the out of domain point, same on both sides
+fn absorb_commitment<F, E>(
+    fri_config: &Friconfig,
+    size: usize,
+    commitment: &[u8],
+    domain_separator: &[u8],
+    public_input: &[u8],
+    transcript: &mut Transcript,
+) -> E
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    <Transcript as TranscriptProtocol<F>>::fri_domain_sep(
+        transcript,
+        domain_separator,
+        size.try_into().unwrap(),
+        public_input.to_vec(),
+    );
+    <Transcript as TranscriptProtocol<F>>::append_config(
+        transcript,
+        fri_config,
+        (size / fri_config.blow_up_factor).try_into().unwrap(),
+    );
+    <Transcript as TranscriptProtocol<F>>::append_commitment(transcript, b"pcs_commit", commitment);
+    <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"ood_point")
+}
+
+/// Commit to the polynomial with coefficients `coeffs` (low degree first). The codeword has
+/// size next_power_of_two(coeffs.len()) * blow_up_factor and lives on domain_offset*H.
+/// Returns the commitment (merkle root) and the data to open it.
+pub fn commit<F>(fri_config: &Friconfig, coeffs: Vec<F>) -> (Vec<u8>, PcsProverData<F>)
+where
+    F: Arithmetic+Field+BigNum+NTTDomain<F> + NTT<F, F>,
+{
+    assert!(!coeffs.is_empty(), "nothing to commit");
+    let size: usize = coeffs.len().next_power_of_two() * fri_config.blow_up_factor;
+    let mut code_word: Current_layer<F> = Current_layer::new();
+    code_word.current_code_word =
+        coeff_to_eval_blowup_coset::<F>(coeffs.clone(), size, F::from_u32(fri_config.domain_offset));
+    let tree = code_word.commit_with_config(&fri_config.commit_config, 1);
+    let commitment: Vec<u8> = tree.get_root::<u8>().unwrap().to_vec();
+    (
+        commitment,
+        PcsProverData {
+            coeffs,
+            code_word,
+            tree,
+        },
+    )
+}
+
+/// Open the committed polynomial at a point z drawn from the transcript: y = p(z) and a FRI
+/// proof for the degree corrected DEEP quotient (p(X) - y)/(X - z)*(1 + r*X).
+/// Zk mode is not supported, the committed codeword is neither masked nor salted.
+pub fn open<F, E>(
+    fri_config: Friconfig,
+    prover_data: &mut PcsProverData<F>,
+    domain_separator: &[u8],
+    public_input: &[u8],
+    transcript: &mut Transcript,
+) -> PcsOpening<E>
+where
+    F: Arithmetic+Field+BigNum+VecOps<F> + NTTDomain<F> + NTT<F, F> + GenerateRandom,
+    E: Arithmetic+Field+BigNum+VecOps<E>,
+{
+    assert!(!fri_config.zk, "pcs does not support zk mode");
+    let size: usize = prover_data.code_word.current_code_word.len();
+    let commitment: Vec<u8> = prover_data.tree.get_root::<u8>().unwrap().to_vec();
+    let point: E = absorb_commitment::<F, E>(
+        &fri_config,
+        size,
+        &commitment,
+        domain_separator,
+        public_input,
+        transcript,
+    );
+    let value: E = evaluate_poly(&lift_vec::<F, E>(&prover_data.coeffs), point);
+    <Transcript as TranscriptProtocol<E>>::append_root(transcript, b"ood_value", &value);
+    debug!("pcs opening at {:?}, value {:?}", point, value);
+    let correction: E =
+        <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"degree_correction");
+
+    //degree corrected quotient on offset*H
+    let rou: F = get_root_of_unity::<F>(size.try_into().unwrap()).unwrap();
+    let mut x: F = F::from_u32(fri_config.domain_offset);
+    let quotient: Vec<E> = prover_data
+        .code_word
+        .current_code_word
+        .iter()
+        .map(|&eval| {
+            let lifted_x = lift::<F, E>(&x);
+            let denominator = lifted_x - point;
+            assert!(denominator != E::zero(), "opening point in the evaluation domain");
+            x = x * rou;
+            (lift::<F, E>(&eval) - value) * denominator.inv() * (E::one() + correction * lifted_x)
+        })
+        .collect();
+
+    let (fri_proof, query_indices) = FriProver::<F, E>::new(fri_config, size.ilog2())
+        .prove_layers(transcript, None, quotient, &[]);
+
+    //open p at the first layer coset of every query
+    let coset_stride = size / fri_config.folding_factor;
+    let mut proofs: Vec<MerkleProof> =
+        Vec::with_capacity(query_indices.len() * fri_config.folding_factor);
+    for &index in query_indices.iter() {
+        for j in 0..fri_config.folding_factor {
+            let leaf: u64 = (index + j * coset_stride).try_into().unwrap();
+            proofs.push(prover_data.code_word.layer_query(leaf, &prover_data.tree));
+        }
+    }
+    PcsOpening {
+        point,
+        value,
+        codeword_opening: BatchOpening::from_proofs(
+            &proofs,
+            &fri_config.commit_config.level_arities(size),
+        ),
+        fri_proof,
+    }
+}
+
+/// Check that the polynomial behind `commitment`, of at most 2^log_domain_size/blow_up_factor
+/// coefficients, evaluates to `opening.value` at `opening.point`, the point the transcript gives.
+pub fn verify_opening<F, E>(
+    fri_config: Friconfig,
+    log_domain_size: u32,
+    commitment: &[u8],
+    opening: &PcsOpening<E>,
+    domain_separator: &[u8],
+    public_input: &[u8],
+    transcript: &mut Transcript,
+) -> Result<(), FriError>
+where
+    F: Arithmetic + BigNum + Field + VecOps<F> + NTTDomain<F> + IntegerRing,
+    E: Arithmetic + BigNum + Field,
+{
+    let size = check_shape(&fri_config, log_domain_size, &opening.fri_proof, 1)?;
+    let k = fri_config.folding_factor;
+    let leafs_per_layer = fri_config.num_queries * k;
+    if opening.codeword_opening.leaf_indices.len() != leafs_per_layer
+        || opening.codeword_opening.leaves.len() != leafs_per_layer
+    {
+        return Err(FriError::MalformedProof(
+            "codeword opening does not contain folding_factor leafs per query",
+        ));
+    }
+    let base_element_size: usize = (F::one()).to_bytes_le().len();
+    opening
+        .codeword_opening
+        .authenticate(
+            commitment,
+            &fri_config.commit_config,
+            size,
+            base_element_size.try_into().unwrap(),
+        )
+        .map_err(|leaf| FriError::MerklePath { layer: 0, leaf })?;
+
+    let point: E = absorb_commitment::<F, E>(
+        &fri_config,
+        size,
+        commitment,
+        domain_separator,
+        public_input,
+        transcript,
+    );
+    if point != opening.point {
+        return Err(FriError::MalformedProof("opening point is not the transcript point"));
+    }
+    <Transcript as TranscriptProtocol<E>>::append_root(transcript, b"ood_value", &opening.value);
+    let correction: E =
+        <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"degree_correction");
+
+    let Ok(rou) = get_root_of_unity::<F>(size as u64) else {
+        return Err(FriError::MalformedProof("no root of unity for domain size"));
+    };
+    let domain_offset: F = F::from_u32(fri_config.domain_offset);
+    let coset_stride = size / k;
+    //the degree corrected quotient at the opened evaluations, which have to sit at index + j*n/k
+    let first_layer = |query_indices: &[usize]| -> Result<Vec<Vec<E>>, FriError> {
+        query_indices
+            .iter()
+            .enumerate()
+            .map(|(q, &index)| {
+                (0..k)
+                    .map(|j| {
+                        let slot = q * k + j;
+                        let expected = index + j * coset_stride;
+                        let found = opening.codeword_opening.leaf_indices[slot] as usize;
+                        if found != expected {
+                            return Err(FriError::QueryIndexMismatch {
+                                query: q,
+                                round: 0,
+                                expected,
+                                found,
+                            });
+                        }
+                        let eval: E =
+                            lift::<F, E>(&F::from_bytes_le(&opening.codeword_opening.leaves[slot]));
+                        let x: E = lift::<F, E>(&(domain_offset * pow(rou, expected as u32)));
+                        if x == opening.point {
+                            return Err(FriError::MalformedProof(
+                                "opening point in the evaluation domain",
+                            ));
+                        }
+                        Ok((eval - opening.value)
+                            * (x - opening.point).inv()
+                            * (E::one() + correction * x))
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+    FriVerifier::<F>::new(fri_config, log_domain_size).verify_layers(
+        size,
+        &opening.fri_proof,
+        transcript,
+        Some(&first_layer),
+        None,
+    )
+}
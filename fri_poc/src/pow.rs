@@ -0,0 +1,78 @@
+use icicle_babybear::field::ScalarField as BabyBear;
+use icicle_core::{bignum::BigNum, hash::HashConfig, traits::Arithmetic};
+use icicle_runtime::memory::HostSlice;
+use rayon::prelude::*;
+
+use crate::{data_structures::HashKind, utils::num_leading_zeros};
+
+// Proof of work: find a nonce with H(challenge || nonce) having at least pow_bits leading zeros.
+// Nonces are searched in batches of POW_BATCH, every batch is one icicle hash call and batches
+// are spread over the rayon threads. The smallest valid nonce is returned, so the prover output
+// does not depend on the number of threads.
+pub const POW_BATCH: u64 = 1 << 12;
+// Poseidon2 over BabyBear hashes one permutation of this width, the input is encoded as u16 limbs
+pub const POSEIDON2_WIDTH: usize = 8;
+
+// bytes hashed for one nonce
+fn pow_input(pow_hash: HashKind, challenge: &[u8], nonce: u64) -> Vec<u8> {
+    let mut input: Vec<u8> = [challenge, &nonce.to_le_bytes()].concat();
+    if pow_hash == HashKind::Poseidon2 {
+        //every two bytes become a canonical field element, zero padded to the width
+        assert!(
+            input.len() <= 2 * POSEIDON2_WIDTH,
+            "challenge too large for a single poseidon2 permutation"
+        );
+        input.resize(2 * POSEIDON2_WIDTH, 0);
+        input = input
+            .chunks(2)
+            .flat_map(|limb| BabyBear::from_u32(u16::from_le_bytes([limb[0], limb[1]]) as u32).to_bytes_le())
+            .collect();
+    }
+    input
+}
+
+// digests of challenge || nonce for all nonces in the range, in one batched hash call
+fn pow_digests(pow_hash: HashKind, challenge: &[u8], nonces: std::ops::Range<u64>) -> Vec<Vec<u8>> {
+    let input_len = pow_input(pow_hash, challenge, 0).len();
+    //poseidon2 input is POSEIDON2_WIDTH elements of 4 bytes, so this is the width 8 permutation
+    let hasher = pow_hash.hasher(input_len as u64).unwrap();
+    let digest_size = hasher.output_size() as usize;
+    let batch = (nonces.end - nonces.start) as usize;
+    let mut input: Vec<u8> = Vec::with_capacity(batch * input_len);
+    for nonce in nonces {
+        input.extend_from_slice(&pow_input(pow_hash, challenge, nonce));
+    }
+    let mut output: Vec<u8> = vec![0u8; batch * digest_size];
+    hasher
+        .hash(
+            HostSlice::from_slice(&input),
+            &HashConfig::default(),
+            HostSlice::from_mut_slice(&mut output),
+        )
+        .unwrap();
+    output.chunks(digest_size).map(|d| d.to_vec()).collect()
+}
+
+/// Digest of a single nonce, what the verifier recomputes.
+pub fn pow_digest(pow_hash: HashKind, challenge: &[u8], nonce: u64) -> Vec<u8> {
+    pow_digests(pow_hash, challenge, nonce..nonce + 1).remove(0)
+}
+
+/// Smallest nonce whose digest has at least `pow_bits` leading zeros.
+pub fn grind(pow_hash: HashKind, pow_bits: usize, challenge: &[u8]) -> u64 {
+    (0..u64::MAX / POW_BATCH)
+        .into_par_iter()
+        .find_map_first(|batch| {
+            let start = batch * POW_BATCH;
+            pow_digests(pow_hash, challenge, start..start + POW_BATCH)
+                .into_iter()
+                .position(|digest| num_leading_zeros(digest) >= pow_bits)
+                .map(|i| start + i as u64)
+        })
+        .expect("No nonce found")
+}
+
+/// Check the nonce, same digest and threshold as `grind`.
+pub fn verify_pow(pow_hash: HashKind, pow_bits: usize, challenge: &[u8], nonce: u64) -> bool {
+    num_leading_zeros(pow_digest(pow_hash, challenge, nonce)) >= pow_bits
+}
@@ -1,8 +1,8 @@
 use std::time::Instant;
 
-use crate::{data_structures::*, transcript::*, utils::*};
+use crate::{data_structures::*, pow::grind, transcript::*, utils::*};
 use icicle_core::{
-    bignum::BigNum, merkle::{MerkleProof, MerkleTree}, ntt::{get_root_of_unity, NTTDomain, NTT}, ring::IntegerRing, traits::{Arithmetic, Invertible}, vec_ops::*
+    bignum::BigNum, merkle::{MerkleProof, MerkleTree}, ntt::{NTTDomain, NTT}, ring::IntegerRing, traits::{Arithmetic, GenerateRandom, Invertible}, vec_ops::*
 };
 use log::{debug, info};
 use icicle_core::field::Field;
@@ -11,107 +11,87 @@ use merlin::Transcript;
 // Prover can input a vector of coefficients, or a vector of evaluations (codeword).
 // However, the verifier only knows about the codeword.
 // The verifier will convince themselves with a collinearity check and Merkle path authentication.
-pub fn prove<F>(
+// Folding challenges are sampled in the extension field E, the base field F is too small
+// for FRI soundness. The first layer is committed as base field leaves, every folded layer
+// (and the final poly) is committed as extension field leaves.
+pub fn prove<F, E>(
     fri_config: Friconfig,
+    domain_separator: &[u8], //protocol label, binds the proof to the caller's protocol
+    public_input: &[u8],     //statement bytes the caller wants the challenges to depend on
     transcript: &mut Transcript,
     code_word: Vec<F>, //evals with blow up factor included
-) -> Friproof<F>
+) -> Friproof<E>
 where
-    F: Arithmetic+Field+BigNum+VecOps<F> + NTTDomain<F> + NTT<F, F>,
+    F: Arithmetic+Field+BigNum+VecOps<F> + NTTDomain<F> + NTT<F, F> + GenerateRandom,
+    E: Arithmetic+Field+BigNum+VecOps<E>,
 {
-    //let protocol_security =
-    let size: usize = code_word.len();
-    debug!("prover_size {:?}", size);
-
-    let precompute_domain = Instant::now();
-    //Begin precompute domain, if NTT domain was exposed could read it directly
-    let rou: F = get_root_of_unity::<F>(size.try_into().unwrap()).unwrap();
-    let rou_inv: F = rou.inv();
-
-    let mut inv_domain: Vec<F> = Vec::with_capacity(size / 2);
-    let mut current = F::one(); // can define coset gen inv here if we want
-    for _ in 0..(size / 2) {
-        inv_domain.push(current);
-        current = current * rou_inv;
-    }
-    let two_inv = F::from_u32(2).inv();
-    //end precompute domain
-
-    let num_rounds = size.ilog2() - fri_config.stopping_size.ilog2();
-    debug!("num_rounds {:?}", num_rounds);
-
-    //init transcript
-    <Transcript as TranscriptProtocol<F>>::fri_domain_sep(
+    let log_size = code_word.len().ilog2();
+    FriProver::<F, E>::new(fri_config, log_size).prove(
+        domain_separator,
+        public_input,
         transcript,
-        b"my_Shitty_FRI",
-        size.try_into().unwrap(),
-        b"public".to_vec(),
-    );
-
-    let mut frilayerdata: Frilayerdata<F> = Frilayerdata::<F>::new();
-
-    let mut friproof: Friproof<F> = Friproof::<F>::new();
-
-    let mut current_layer: Current_layer<F> = Current_layer::new();
-    current_layer.current_code_word = code_word.clone();
+        code_word,
+    )
+}
 
-    info!(
-        "prove: Precompute domain inverse {:?}",
-        precompute_domain.elapsed()
+// entry checks of a codeword of `size` for a prover of up to 2^max_log_size elements,
+// shared by `FriProver` and `DeviceFriProver`
+pub(crate) fn check_size(fri_config: &Friconfig, max_log_size: u32, size: usize) {
+    assert!(
+        size <= 1 << max_log_size,
+        "codeword larger than the prover's max size"
     );
-    let commit_phase = Instant::now();
-    //commit phase
-    for j in 0..num_rounds {
-        debug!(
-            "round: {:?}, current_code_word: {:?}",
-            j,
-            current_layer.current_code_word.clone()
-        );
+    assert!(
+        fri_config.folding_factor.is_power_of_two() && fri_config.folding_factor > 1,
+        "folding factor should be a power of two"
+    );
+    assert!(
+        size.is_power_of_two() && fri_config.stopping_size.is_power_of_two(),
+        "codeword size and stopping size should be powers of two"
+    );
+    assert!(
+        size >= fri_config.stopping_size,
+        "codeword smaller than the stopping size"
+    );
+    assert_eq!(
+        (size.ilog2() - fri_config.stopping_size.ilog2()) % fri_config.log_folding_factor(),
+        0,
+        "size/stopping_size should be a power of the folding factor"
+    );
+    assert!(
+        fri_config.blow_up_factor.is_power_of_two()
+            && fri_config.blow_up_factor <= fri_config.stopping_size,
+        "blow up factor should be a power of two at most the stopping size"
+    );
+    assert!(
+        fri_config.num_queries <= size / fri_config.folding_factor,
+        "num_queries distinct queries need as many top layer cosets"
+    );
+    assert!(
+        fri_config.commit_config.is_valid(),
+        "commit config should have arity 2, 4 or 8 and poseidon2 leafs iff poseidon2 compression"
+    );
+}
 
-        //add current code word to prover list
-        frilayerdata
-            .layer_code_words
-            .push(current_layer.current_code_word.clone());
-
-        //compute merkle commit
-        let current_tree: MerkleTree = current_layer.commit();
-
-        //extract root for FS challenge
-        let current_root: F = current_tree.get_root::<F>().unwrap()[0];
-
-        //add tree to prover list
-        debug!("round: {:?}, Current_root: {:?}", j, current_root);
-        frilayerdata.layer_trees.push(current_tree);
-
-        //generate fiat shamir challenge
-        <Transcript as TranscriptProtocol<F>>::append_root(transcript, b"commit", &current_root);
-        let current_challenge: F =
-            <Transcript as TranscriptProtocol<F>>::challenge_scalar(transcript, b"challenge");
-        debug!("round: {:?},Current_challenge {:?}", j, current_challenge);
-
-        //fold without precompute
-        //current_layer.current_code_word=current_layer.fold_evals( F::one(), current_challenge);
-        let mut inv_domain_layer: Vec<F> =
-            inv_domain.iter().step_by(2_usize.pow(j)).cloned().collect();
-        current_layer.current_code_word = current_layer.fold_evals_precompute_domain(
-            &mut inv_domain_layer,
-            &two_inv,
-            current_challenge,
-        );
-        if current_layer.current_code_word.len() == fri_config.stopping_size {
-            friproof.final_poly = current_layer.current_code_word.clone();
-            debug!("Final poly {:?}", friproof.final_poly);
-            break;
-        }
-    }
-    info!("prove: Commit phase {:?}", commit_phase.elapsed());
-    set_backend_cpu();
+//grinding and query sampling after the last commitment, returns the top layer query indices
+pub(crate) fn sample_queries<F, E>(
+    fri_config: &Friconfig,
+    transcript: &mut Transcript,
+    friproof: &mut Friproof<E>,
+    size: usize,
+) -> Vec<usize>
+where
+    F: Arithmetic + Field + BigNum,
+{
     let pow_time = Instant::now();
-    //proof of work is not parallelized yet so better do query and pow in cpu
     let current_challenge: F =
         <Transcript as TranscriptProtocol<F>>::challenge_scalar(transcript, b"challenge");
     debug!("POW_challenge {:?}", current_challenge);
-    let nonce: u64 = proof_of_work::<F>(fri_config.pow_bits, current_challenge);
+    let nonce: u64 = grind(
+        fri_config.pow_hash,
+        fri_config.pow_bits,
+        &current_challenge.to_bytes_le(),
+    );
     debug!("nonce {:?}", nonce);
     info!("prove: pow_phase {:?}", pow_time.elapsed());
 
@@ -120,41 +100,567 @@ where
     //add nonce to proof struct
     friproof.pow_nonce = nonce;
 
-    //sample queries for foldiing by 2
-    let seed = <Transcript as TranscriptProtocol<F>>::challenge_scalar(transcript, b"sample")
-        .to_bytes_le();
-    debug!("prover_seed for sampling based on transcript {:?}", seed);
-    let query_indices: Vec<usize> = generate_samples_in_range(
-        seed,
+    //sample queries for folding by k, one index per coset of the top layer
+    let (query_indices, duplicates) = sample_distinct_queries::<F>(
+        transcript,
         fri_config.num_queries,
         size / fri_config.folding_factor,
     );
+    info!("prove: {:?} duplicate query draws dropped", duplicates);
     debug!("top layer query_indices {:?}", query_indices);
+    query_indices
+}
 
-    //query phase
-    let query_time = Instant::now();
-    //iterate over indices in query vector
+//merkle proofs of every query of a layer of `layer_size`, `open` proves one leaf
+pub(crate) fn open_layer(
+    fri_config: &Friconfig,
+    query_indices: &[usize],
+    layer_size: usize,
+    open: impl Fn(u64) -> MerkleProof,
+) -> Vec<MerkleProof> {
+    let coset_stride = layer_size / fri_config.folding_factor;
+    //Single: index and its k-1 siblings index + i*n/k that fold together, one leaf each
+    //Coset: leaf index holds the whole coset
+    let leafs_per_query = fri_config.folding_factor / fri_config.elements_per_leaf();
+    let mut layer_proofs: Vec<MerkleProof> =
+        Vec::with_capacity(query_indices.len() * leafs_per_query);
     for query_index in query_indices.iter() {
-        //for each query index go over all the fri layers,
-        for (layer_code_word, layer_tree) in frilayerdata
-            .layer_code_words
+        let index = query_index % coset_stride;
+        for i in 0..leafs_per_query {
+            layer_proofs.push(open((index + i * coset_stride).try_into().unwrap()));
+        }
+    }
+    layer_proofs
+}
+
+/// What the prover keeps from the commit phase for the query phase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProverMemory {
+    /// every layer in leaf order and full merkle trees, layers fold with vec ops
+    Full,
+    /// only the first layer, in leaf order, and merkle trees without their `tree_min_layer`
+    /// lowest levels, which are rehashed from the leaves for every opening. Folded layers are
+    /// dropped once committed and refolded one at a time in the query phase, with host folds
+    /// over base field twiddles. Peak memory is estimated by `security::prover_peak_memory`.
+    Low { tree_min_layer: u64 },
+}
+
+/// Prover for one `Friconfig` and codewords of up to 2^max_log_size elements. The setup work,
+/// NTT domain, inverse twiddles of every layer length and 1/2, is done once in `new` and
+/// reused by every proof, so proving many polynomials skips it.
+pub struct FriProver<F, E> {
+    pub fri_config: Friconfig,
+    pub max_log_size: u32,
+    pub memory: ProverMemory,
+    inv_twiddles: Vec<Vec<E>>, //subgroup twiddles, lifted so the extension layers fold with vec ops
+    base_twiddles: Vec<Vec<F>>, //Low: the same twiddles in the base field, lifted per element
+    two_inv: E,
+}
+
+impl<F, E> FriProver<F, E>
+where
+    F: Arithmetic+Field+BigNum+VecOps<F> + NTTDomain<F> + NTT<F, F> + GenerateRandom,
+    E: Arithmetic+Field+BigNum+VecOps<E>,
+{
+    pub fn new(fri_config: Friconfig, max_log_size: u32) -> Self {
+        Self::with_memory(fri_config, max_log_size, ProverMemory::Full)
+    }
+
+    pub fn with_memory(fri_config: Friconfig, max_log_size: u32, memory: ProverMemory) -> Self {
+        assert!(max_log_size > 0, "max log size should be positive");
+        assert_ne!(fri_config.domain_offset, 0, "domain offset should be non zero");
+        assert!(
+            memory == ProverMemory::Full || !fri_config.zk,
+            "the low memory prover does not support zk, salted leaves are a copy of every layer"
+        );
+        let precompute_domain = Instant::now();
+        init_ntt_domain::<F>(1 << max_log_size);
+        //the coset offset of each layer is applied to the challenge, the twiddles are the subgroup's
+        let twiddles: Vec<Vec<F>> = inverse_twiddles::<F>(max_log_size);
+        let (inv_twiddles, base_twiddles): (Vec<Vec<E>>, Vec<Vec<F>>) = match memory {
+            ProverMemory::Full => (
+                twiddles.iter().map(|twiddles| lift_vec::<F, E>(twiddles)).collect(),
+                Vec::new(),
+            ),
+            ProverMemory::Low { .. } => (Vec::new(), twiddles),
+        };
+        info!(
+            "FriProver: Precompute domain inverse {:?}",
+            precompute_domain.elapsed()
+        );
+        FriProver {
+            fri_config,
+            max_log_size,
+            memory,
+            inv_twiddles,
+            base_twiddles,
+            two_inv: E::from_u32(2).inv(),
+        }
+    }
+
+    /// Codeword of `coeffs` for this config: size next_power_of_two(coeffs.len())*blow_up_factor
+    /// on domain_offset*H, on the NTT domain set up in `new`.
+    pub fn lde(&self, coeffs: Vec<F>) -> Vec<F> {
+        let size: usize = coeffs.len().next_power_of_two() * self.fri_config.blow_up_factor;
+        assert!(
+            size <= 1 << self.max_log_size,
+            "codeword larger than the prover's max size"
+        );
+        coset_lde(coeffs, size, F::from_u32(self.fri_config.domain_offset))
+    }
+
+    /// Same as `prove` with this prover's config.
+    pub fn prove(
+        &self,
+        domain_separator: &[u8],
+        public_input: &[u8],
+        transcript: &mut Transcript,
+        code_word: Vec<F>,
+    ) -> Friproof<E> {
+        let fri_config = self.fri_config;
+        let size: usize = code_word.len();
+        //init transcript
+        <Transcript as TranscriptProtocol<F>>::fri_domain_sep(
+            transcript,
+            domain_separator,
+            size.try_into().unwrap(),
+            public_input.to_vec(),
+        );
+        //everything the proof is checked against goes in before the first commitment
+        <Transcript as TranscriptProtocol<F>>::append_config(
+            transcript,
+            &fri_config,
+            (size / fri_config.blow_up_factor).try_into().unwrap(),
+        );
+        match self.memory {
+            ProverMemory::Full => {
+                let lifted: Vec<E> = lift_vec::<F, E>(&code_word);
+                self.prove_layers(transcript, Some(code_word), lifted, &[]).0
+            }
+            ProverMemory::Low { tree_min_layer } => {
+                self.prove_low_memory(transcript, code_word, tree_min_layer)
+            }
+        }
+    }
+
+    /// Commit, fold and query phase on `code_word`, once the statement is in the transcript.
+    /// With `base_code_word` (the same codeword in the base field) the first layer is committed
+    /// and opened like every other layer. Without it the caller has committed the first layer
+    /// itself, round 0 only folds, and the caller opens the first layer at the returned top layer
+    /// query indices. A non empty `roll_ins[r]` (r > 0) is added to layer r before its commitment,
+    /// batch uses it for columns shorter than the first layer.
+    pub(crate) fn prove_layers(
+        &self,
+        transcript: &mut Transcript,
+        base_code_word: Option<Vec<F>>,
+        code_word: Vec<E>,
+        roll_ins: &[Vec<E>],
+    ) -> (Friproof<E>, Vec<usize>) {
+        let fri_config = self.fri_config;
+        let size: usize = code_word.len();
+        debug!("prover_size {:?}", size);
+        assert_eq!(self.memory, ProverMemory::Full, "prove_layers keeps every layer");
+        check_size(&fri_config, self.max_log_size, size);
+        //layer r lives on g^{k^r} H_r
+        let mut layer_offset_inv: E = lift::<F, E>(&F::from_u32(fri_config.domain_offset).inv());
+        let num_rounds = fri_config.num_rounds(size);
+        debug!("num_rounds {:?}", num_rounds);
+        let elements_per_leaf = fri_config.elements_per_leaf();
+
+        let mut frilayerdata: Frilayerdata<F, E> = Frilayerdata::<F, E>::new();
+
+        let mut friproof: Friproof<E> = Friproof::<E>::new();
+
+        //first layer is committed in the base field, unless the caller committed it
+        let first_round: usize = if base_code_word.is_some() { 0 } else { 1 };
+        assert!(
+            !fri_config.zk || first_round == 0,
+            "zk mode masks the first layer, it has to be committed here"
+        );
+        let mut base_layer: Current_layer<F> = Current_layer::new();
+        base_layer.current_code_word = base_code_word.unwrap_or_default();
+        //and folded in the extension field
+        let mut current_layer: Current_layer<E> = Current_layer::new();
+        current_layer.current_code_word = code_word;
+        if fri_config.zk {
+            //add a random codeword of the same degree bound before anything is committed, the
+            //openings and every folded layer are of input + mask, never of the input alone
+            let mask: Vec<F> = coset_lde::<F>(
+                generate_random_vector::<F>(size / fri_config.blow_up_factor),
+                size,
+                F::from_u32(fri_config.domain_offset),
+            );
+            base_layer.current_code_word = base_layer
+                .current_code_word
+                .iter()
+                .zip(mask.iter())
+                .map(|(&value, &mask_value)| value + mask_value)
+                .collect();
+            current_layer.current_code_word = lift_vec::<F, E>(&base_layer.current_code_word);
+        }
+
+        let commit_phase = Instant::now();
+        //commit phase
+        for j in 0..num_rounds {
+            if let Some(roll_in) = roll_ins.get(j as usize).filter(|roll_in| !roll_in.is_empty()) {
+                assert!(
+                    j > 0 && roll_in.len() == current_layer.current_code_word.len(),
+                    "roll ins should have the length of a folded layer"
+                );
+                current_layer.current_code_word = current_layer
+                    .current_code_word
+                    .iter()
+                    .zip(roll_in.iter())
+                    .map(|(&value, &roll_in_value)| value + roll_in_value)
+                    .collect();
+            }
+            debug!(
+                "round: {:?}, current_code_word: {:?}",
+                j,
+                current_layer.current_code_word.clone()
+            );
+
+            //add current code word to prover list, in leaf order, and compute merkle commit
+            let current_tree: Option<MerkleTree> = if (j as usize) < first_round {
+                None
+            } else if j == 0 {
+                let mut leaf_layer: Current_layer<F> = Current_layer::new();
+                leaf_layer.current_code_word = match fri_config.leaf_layout {
+                    LeafLayout::Single => base_layer.current_code_word.clone(),
+                    LeafLayout::Coset => base_layer.coset_leaf_order(fri_config.folding_factor),
+                };
+                let tree = if fri_config.zk {
+                    let (tree, salted_leaves) = leaf_layer.commit_with_salt(
+                        &fri_config.commit_config,
+                        elements_per_leaf,
+                        fri_config.salt_bytes(),
+                    );
+                    frilayerdata.layer_salted_leaves.push(salted_leaves);
+                    tree
+                } else {
+                    leaf_layer.commit_with_config(&fri_config.commit_config, elements_per_leaf)
+                };
+                frilayerdata.base_code_word = leaf_layer.current_code_word;
+                Some(tree)
+            } else {
+                let mut leaf_layer: Current_layer<E> = Current_layer::new();
+                leaf_layer.current_code_word = match fri_config.leaf_layout {
+                    LeafLayout::Single => current_layer.current_code_word.clone(),
+                    LeafLayout::Coset => current_layer.coset_leaf_order(fri_config.folding_factor),
+                };
+                let tree = if fri_config.zk {
+                    let (tree, salted_leaves) = leaf_layer.commit_with_salt(
+                        &fri_config.commit_config,
+                        elements_per_leaf,
+                        fri_config.salt_bytes(),
+                    );
+                    frilayerdata.layer_salted_leaves.push(salted_leaves);
+                    tree
+                } else {
+                    leaf_layer.commit_with_config(&fri_config.commit_config, elements_per_leaf)
+                };
+                frilayerdata
+                    .layer_code_words
+                    .push(leaf_layer.current_code_word);
+                Some(tree)
+            };
+
+            if let Some(current_tree) = current_tree {
+                //extract root for FS challenge, all of its bytes go in the transcript
+                let current_root: Vec<u8> = current_tree.get_root::<u8>().unwrap().to_vec();
+
+                //add tree to prover list
+                debug!("round: {:?}, Current_root: {:?}", j, current_root);
+                frilayerdata.layer_trees.push(current_tree);
+
+                <Transcript as TranscriptProtocol<F>>::append_commitment(transcript, b"commit", &current_root);
+                friproof.layer_commitments.push(current_root);
+            }
+            //generate fiat shamir challenge
+            let current_challenge: E =
+                <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"challenge");
+            debug!("round: {:?},Current_challenge {:?}", j, current_challenge);
+
+            //fold without precompute
+            //current_layer.current_code_word=current_layer.fold_evals( F::one(), current_challenge);
+            current_layer.fold_with_twiddles(
+                &self.inv_twiddles,
+                &self.two_inv,
+                current_challenge,
+                layer_offset_inv,
+                fri_config.folding_factor,
+            );
+            layer_offset_inv = pow(layer_offset_inv, fri_config.folding_factor as u32);
+            if fri_config.fri_mode == FriMode::Deep {
+                //the fold g is fixed by the commitment and the challenge, send g(z) and
+                //continue with (g(X) - g(z))/(X - z)
+                let layer_offset: E = layer_offset_inv.inv();
+                let deep_point: E =
+                    <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"deep_point");
+                let deep_value: E = evaluate_coset::<F, E>(
+                    &current_layer.current_code_word,
+                    layer_offset,
+                    deep_point,
+                );
+                <Transcript as TranscriptProtocol<E>>::append_root(transcript, b"deep_value", &deep_value);
+                debug!("round: {:?}, deep point {:?}, value {:?}", j, deep_point, deep_value);
+                friproof.deep_values.push(deep_value);
+                current_layer.current_code_word = deep_quotient::<F, E>(
+                    &current_layer.current_code_word,
+                    layer_offset,
+                    deep_point,
+                    deep_value,
+                );
+            }
+            if current_layer.current_code_word.len() == fri_config.stopping_size {
+                //send the last layer in coefficient form, it lives on g^{k^r}H_stop
+                //for an honest prover everything above the degree bound is zero
+                let mut final_coeffs: Vec<E> =
+                    interpolate_coset::<F, E>(&current_layer.current_code_word, layer_offset_inv);
+                final_coeffs.truncate(fri_config.final_poly_len());
+                friproof.final_poly = final_coeffs;
+                debug!("Final poly {:?}", friproof.final_poly);
+                break;
+            }
+        }
+        info!("prove: Commit phase {:?}", commit_phase.elapsed());
+        //pow is batched hashing over the rayon threads and the query phase is host side, both on cpu
+        set_backend_cpu();
+        let query_indices: Vec<usize> =
+            sample_queries::<F, E>(&fri_config, transcript, &mut friproof, size);
+
+        //query phase
+        let query_time = Instant::now();
+        let mut individual_bytes: usize = 0;
+        //for each fri layer, open the coset of every query index and batch the paths of the layer
+        for (i, layer_tree) in frilayerdata.layer_trees.iter().enumerate() {
+            let j = i + first_round;
+            let layer_size = size >> (j * fri_config.log_folding_factor() as usize);
+            //layers are stored in leaf order, openings read them in place
+            let layer_proofs: Vec<MerkleProof> = open_layer(&fri_config, &query_indices, layer_size, |sibling| {
+                if fri_config.zk {
+                    Current_layer::<F>::salted_query(
+                        &frilayerdata.layer_salted_leaves[i],
+                        sibling,
+                        layer_tree,
+                    )
+                } else if j == 0 {
+                    Current_layer::<F>::leaf_query(&frilayerdata.base_code_word, sibling, layer_tree)
+                } else {
+                    Current_layer::<E>::leaf_query(
+                        &frilayerdata.layer_code_words[j - 1],
+                        sibling,
+                        layer_tree,
+                    )
+                }
+            });
+            individual_bytes += individual_proofs_num_bytes(&layer_proofs);
+            let arities = fri_config
+                .commit_config
+                .level_arities(layer_size / elements_per_leaf);
+            friproof
+                .layer_openings
+                .push(BatchOpening::from_proofs(&layer_proofs, &arities));
+        }
+        let batch_bytes: usize = friproof
+            .layer_openings
             .iter()
-            .zip(frilayerdata.layer_trees.iter())
-        {
-            current_layer.current_code_word = layer_code_word.clone();
-            let layer_size = layer_code_word.len();
-            let index: u64 = (query_index % layer_size).try_into().unwrap();
-            let index_sym: u64 = ((query_index + layer_size / 2) % layer_size)
-                .try_into()
-                .unwrap();
-            let index_proof: MerkleProof = current_layer.layer_query(index, layer_tree);
-            let index_sym_proof: MerkleProof = current_layer.layer_query(index_sym, layer_tree);
+            .map(|opening| opening.num_bytes())
+            .sum();
+        info!(
+            "prove: merkle openings {:?} bytes batched, {:?} bytes as individual paths, saved {:?} bytes",
+            batch_bytes,
+            individual_bytes,
+            individual_bytes.saturating_sub(batch_bytes)
+        );
+        info!("prove: query phase {:?}", query_time.elapsed());
+        drop(frilayerdata);
+        (friproof, query_indices)
+    }
+
+    //binary fold of a layer of `len` elements read through `value`, with x^{-1} = shift_inv*w^{-i}
+    //from the base field twiddles: (v(x) + v(-x))/2 + alpha*(v(x) - v(-x))/2x
+    fn host_fold(&self, len: usize, value: impl Fn(usize) -> E, alpha: E, shift_inv: E) -> Vec<E> {
+        let half: usize = len / 2;
+        let twiddles: &[F] = &self.base_twiddles[len.ilog2() as usize];
+        let weight: E = alpha * shift_inv * self.two_inv;
+        (0..half)
+            .map(|i| {
+                let (v1, v2) = (value(i), value(i + half));
+                (v1 + v2) * self.two_inv + (v1 - v2) * lift::<F, E>(&twiddles[i]) * weight
+            })
+            .collect()
+    }
+
+    //fold by k like `Current_layer::fold_with_twiddles`, round 0 reads the first layer from its
+    //leaf order and lifts one element at a time
+    fn fold_round(
+        &self,
+        base_leaves: &[F],
+        layer: &[E],
+        round: usize,
+        alpha: E,
+        shift_inv: E,
+    ) -> Vec<E> {
+        let folding_factor = self.fri_config.folding_factor;
+        let mut folded: Vec<E> = if round == 0 {
+            let n: usize = base_leaves.len();
+            let coset_stride: usize = n / folding_factor;
+            let position = |i: usize| match self.fri_config.leaf_layout {
+                LeafLayout::Single => i,
+                LeafLayout::Coset => (i % coset_stride) * folding_factor + i / coset_stride,
+            };
+            self.host_fold(n, |i| lift::<F, E>(&base_leaves[position(i)]), alpha, shift_inv)
+        } else {
+            self.host_fold(layer.len(), |i| layer[i], alpha, shift_inv)
+        };
+        let mut alpha_pow = alpha;
+        let mut shift_inv_pow = shift_inv;
+        for _ in 1..folding_factor.ilog2() {
+            alpha_pow = alpha_pow * alpha_pow;
+            shift_inv_pow = shift_inv_pow * shift_inv_pow;
+            folded = self.host_fold(folded.len(), |i| folded[i], alpha_pow, shift_inv_pow);
+        }
+        folded
+    }
+
+    /// `ProverMemory::Low` commit, fold and query phase. Same transcript and proof as
+    /// `prove_layers` with the first layer committed here.
+    fn prove_low_memory(
+        &self,
+        transcript: &mut Transcript,
+        code_word: Vec<F>,
+        tree_min_layer: u64,
+    ) -> Friproof<E> {
+        let fri_config = self.fri_config;
+        let size: usize = code_word.len();
+        debug!("prover_size {:?}", size);
+        check_size(&fri_config, self.max_log_size, size);
+        let offset_inv: E = lift::<F, E>(&F::from_u32(fri_config.domain_offset).inv());
+        let mut layer_offset_inv: E = offset_inv;
+        let num_rounds = fri_config.num_rounds(size);
+        let elements_per_leaf = fri_config.elements_per_leaf();
+        let commit_config = fri_config.commit_config;
+
+        let mut friproof: Friproof<E> = Friproof::<E>::new();
+        //the input is only kept in leaf order
+        let mut base_layer: Current_layer<F> = Current_layer::new();
+        base_layer.current_code_word = code_word;
+        if fri_config.leaf_layout == LeafLayout::Coset {
+            base_layer.current_code_word = base_layer.coset_leaf_order(fri_config.folding_factor);
+        }
+        let mut current_layer: Current_layer<E> = Current_layer::new();
+        let mut layer_trees: Vec<MerkleTree> = Vec::with_capacity(num_rounds as usize);
+        let mut challenges: Vec<E> = Vec::with_capacity(num_rounds as usize);
+        let mut deep_points: Vec<E> = Vec::new();
+
+        let commit_phase = Instant::now();
+        for j in 0..num_rounds as usize {
+            let layer_tree: MerkleTree = if j == 0 {
+                base_layer.commit_pruned(&commit_config, elements_per_leaf, tree_min_layer)
+            } else if fri_config.leaf_layout == LeafLayout::Coset {
+                //leaf order copy of a layer, dropped after the commit
+                let mut leaf_layer: Current_layer<E> = Current_layer::new();
+                leaf_layer.current_code_word = current_layer.coset_leaf_order(fri_config.folding_factor);
+                leaf_layer.commit_pruned(&commit_config, elements_per_leaf, tree_min_layer)
+            } else {
+                current_layer.commit_pruned(&commit_config, elements_per_leaf, tree_min_layer)
+            };
+            let current_root: Vec<u8> = layer_tree.get_root::<u8>().unwrap().to_vec();
+            debug!("round: {:?}, Current_root: {:?}", j, current_root);
+            <Transcript as TranscriptProtocol<F>>::append_commitment(transcript, b"commit", &current_root);
+            friproof.layer_commitments.push(current_root);
+            layer_trees.push(layer_tree);
+
+            let current_challenge: E =
+                <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"challenge");
+            debug!("round: {:?},Current_challenge {:?}", j, current_challenge);
+            challenges.push(current_challenge);
+            current_layer.current_code_word = self.fold_round(
+                &base_layer.current_code_word,
+                &current_layer.current_code_word,
+                j,
+                current_challenge,
+                layer_offset_inv,
+            );
+            layer_offset_inv = pow(layer_offset_inv, fri_config.folding_factor as u32);
+            if fri_config.fri_mode == FriMode::Deep {
+                let layer_offset: E = layer_offset_inv.inv();
+                let deep_point: E =
+                    <Transcript as TranscriptProtocol<E>>::challenge_scalar(transcript, b"deep_point");
+                let deep_value: E =
+                    evaluate_coset::<F, E>(&current_layer.current_code_word, layer_offset, deep_point);
+                <Transcript as TranscriptProtocol<E>>::append_root(transcript, b"deep_value", &deep_value);
+                friproof.deep_values.push(deep_value);
+                deep_points.push(deep_point);
+                current_layer.current_code_word = deep_quotient::<F, E>(
+                    &current_layer.current_code_word,
+                    layer_offset,
+                    deep_point,
+                    deep_value,
+                );
+            }
+        }
+        let mut final_coeffs: Vec<E> =
+            interpolate_coset::<F, E>(&current_layer.current_code_word, layer_offset_inv);
+        final_coeffs.truncate(fri_config.final_poly_len());
+        friproof.final_poly = final_coeffs;
+        info!("prove: Commit phase {:?}", commit_phase.elapsed());
+        //pow is batched hashing over the rayon threads and the query phase is host side, both on cpu
+        set_backend_cpu();
+        let query_indices: Vec<usize> =
+            sample_queries::<F, E>(&fri_config, transcript, &mut friproof, size);
+
+        //query phase: refold the layers one at a time with the same challenges
+        let query_time = Instant::now();
+        current_layer.current_code_word = Vec::new();
+        layer_offset_inv = offset_inv;
+        for (j, layer_tree) in layer_trees.iter().enumerate() {
+            let layer_size = size >> (j * fri_config.log_folding_factor() as usize);
+            let coset_leaves: Vec<E> = if j > 0 && fri_config.leaf_layout == LeafLayout::Coset {
+                current_layer.coset_leaf_order(fri_config.folding_factor)
+            } else {
+                Vec::new()
+            };
+            let layer_proofs: Vec<MerkleProof> = open_layer(&fri_config, &query_indices, layer_size, |sibling| {
+                if j == 0 {
+                    Current_layer::<F>::leaf_query(&base_layer.current_code_word, sibling, layer_tree)
+                } else if fri_config.leaf_layout == LeafLayout::Coset {
+                    Current_layer::<E>::leaf_query(&coset_leaves, sibling, layer_tree)
+                } else {
+                    Current_layer::<E>::leaf_query(
+                        &current_layer.current_code_word,
+                        sibling,
+                        layer_tree,
+                    )
+                }
+            });
+            drop(coset_leaves);
+            let arities = commit_config.level_arities(layer_size / elements_per_leaf);
             friproof
-                .query_proofs
-                .push(vec![index_proof, index_sym_proof]);
+                .layer_openings
+                .push(BatchOpening::from_proofs(&layer_proofs, &arities));
+            if j + 1 == layer_trees.len() {
+                break;
+            }
+            current_layer.current_code_word = self.fold_round(
+                &base_layer.current_code_word,
+                &current_layer.current_code_word,
+                j,
+                challenges[j],
+                layer_offset_inv,
+            );
+            layer_offset_inv = pow(layer_offset_inv, fri_config.folding_factor as u32);
+            if fri_config.fri_mode == FriMode::Deep {
+                current_layer.current_code_word = deep_quotient::<F, E>(
+                    &current_layer.current_code_word,
+                    layer_offset_inv.inv(),
+                    deep_points[j],
+                    friproof.deep_values[j],
+                );
+            }
         }
+        info!("prove: query phase {:?}", query_time.elapsed());
+        friproof
     }
-    info!("prove: query phase {:?}", query_time.elapsed());
-    drop(frilayerdata);
-    friproof
 }
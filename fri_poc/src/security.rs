@@ -0,0 +1,309 @@
+use icicle_core::{bignum::BigNum, field::Field, traits::Arithmetic};
+
+use crate::{data_structures::*, prover::ProverMemory};
+
+// Soundness estimates for FRI, rate rho = 1/blow_up_factor, n = domain size, |F| the size of
+// the extension field the challenges live in, s = num_queries distinct positions.
+// conjectured (ethSTARK): every query gives log2(1/rho) bits, plus grinding
+// unique decoding: eps <= n*sum_i(k_i-1)/|F| + ((1+rho)/2)^s
+// Johnson (BCIKS20, as summarised in Haböck "A summary on the FRI low degree test"), m >= 3:
+//   eps <= (m+1/2)^7/(3 rho^{3/2}) n^2/|F| + (2m+1)(n+1)/sqrt(rho) sum_i(k_i-1)/|F|
+//          + (sqrt(rho)(1+1/2m))^s
+// grinding divides the query term by 2^pow_bits, every estimate is capped by hash collisions.
+// The bounds are the same for `FriMode::Deep`, the mode only changes the proof size.
+
+/// Security level in bits and expected proof size of a `Friconfig` for a given domain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SecurityReport {
+    /// bits under the ethSTARK conjecture
+    pub conjectured_bits: f64,
+    /// provable bits with proximity (1-rho)/2
+    pub unique_decoding_bits: f64,
+    /// provable bits with proximity up to 1-sqrt(rho), best m in the BCIKS20 bound
+    pub johnson_bits: f64,
+    /// expected size of the serialized openings, final poly and nonce
+    pub proof_size_bytes: usize,
+}
+
+/// log2 of the field size, from p-1 of the base field `F` and the degree of `E` over it
+pub fn field_bits<F, E>() -> f64
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    let base_bytes = F::one().to_bytes_le().len();
+    let degree = E::one().to_bytes_le().len() / base_bytes;
+    let p_minus_one: f64 = (F::zero() - F::one())
+        .to_bytes_le()
+        .iter()
+        .rev()
+        .fold(0f64, |acc, &byte| acc * 256.0 + byte as f64);
+    (p_minus_one + 1.0).log2() * degree as f64
+}
+
+// -log2 of a sum of errors given as log2 values, without underflowing
+fn bits_of(log_errors: &[f64]) -> f64 {
+    let max = log_errors.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let sum: f64 = log_errors.iter().map(|e| (e - max).exp2()).sum();
+    -(max + sum.log2())
+}
+
+// expected distinct nodes of a tree level with 2^width nodes, when q uniform queries each
+// open 2^log_m leaves spread evenly over the tree (the siblings of a coset)
+fn expected_known_nodes(width: u32, log_m: u32, q: f64) -> f64 {
+    if width <= log_m {
+        return 2f64.powi(width as i32);
+    }
+    let subtree = 2f64.powi((width - log_m) as i32);
+    2f64.powi(width as i32) * (1.0 - (1.0 - 1.0 / subtree).powf(q))
+}
+
+impl SecurityReport {
+    /// Estimate security and proof size of `fri_config` for a codeword of 2^log_domain_size,
+    /// committed with a hash of `hash_bytes` output, base field `F` and extension `E`.
+    pub fn new<F, E>(fri_config: &Friconfig, log_domain_size: u32, hash_bytes: usize) -> Self
+    where
+        F: Arithmetic + Field + BigNum,
+        E: Arithmetic + Field + BigNum,
+    {
+        let log_field = field_bits::<F, E>();
+        let hash_bits = 4.0 * hash_bytes as f64;
+        let log_n = log_domain_size as f64;
+        let rho = 1.0 / fri_config.blow_up_factor as f64;
+        let s = fri_config.num_queries as f64;
+        let pow = fri_config.pow_bits as f64;
+        let size: usize = 1 << log_domain_size;
+        let num_rounds = fri_config.num_rounds(size);
+        //each k-ary round is a random combination of k-1 polys
+        let sum_k: f64 = (num_rounds as f64 * (fri_config.folding_factor - 1) as f64).max(1.0);
+
+        let conjectured_bits = (s * rho.recip().log2() + pow)
+            .min(log_field - log_n)
+            .min(hash_bits);
+
+        let unique_decoding_bits = bits_of(&[
+            log_n + sum_k.log2() - log_field,
+            s * ((1.0 + rho) / 2.0).log2() - pow,
+        ])
+        .min(hash_bits);
+
+        let johnson_bits = (3..=64)
+            .map(|m| {
+                let m = m as f64;
+                bits_of(&[
+                    7.0 * (m + 0.5).log2() - (3.0 * rho.powf(1.5)).log2() + 2.0 * log_n
+                        - log_field,
+                    ((2.0 * m + 1.0) * (size as f64 + 1.0) / rho.sqrt()).log2() + sum_k.log2()
+                        - log_field,
+                    s * (rho.sqrt() * (1.0 + 1.0 / (2.0 * m))).log2() - pow,
+                ])
+            })
+            .fold(f64::NEG_INFINITY, f64::max)
+            .min(hash_bits);
+
+        SecurityReport {
+            conjectured_bits,
+            unique_decoding_bits,
+            johnson_bits,
+            proof_size_bytes: expected_proof_size::<F, E>(fri_config, log_domain_size, hash_bytes),
+        }
+    }
+}
+
+/// Expected `Friproof::num_bytes` for uniform query positions: per layer the root, the opened
+/// leaves with their indices and the expected number of deduplicated authentication nodes.
+/// `hash_bytes` is the digest size of the commit config hashes.
+pub fn expected_proof_size<F, E>(
+    fri_config: &Friconfig,
+    log_domain_size: u32,
+    hash_bytes: usize,
+) -> usize
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    let base_element_size = F::one().to_bytes_le().len();
+    let ext_element_size = E::one().to_bytes_le().len();
+    let elements_per_leaf = fri_config.elements_per_leaf();
+    let leafs_per_query = fri_config.folding_factor / elements_per_leaf;
+    let log_m = leafs_per_query.ilog2();
+    let q = fri_config.num_queries as f64;
+    let num_rounds = fri_config.num_rounds(1 << log_domain_size);
+
+    let mut bytes: f64 = 0.0;
+    let mut log_layer_size = log_domain_size;
+    for r in 0..num_rounds {
+        //zk: every leaf has its salt
+        let leaf_elements = if r == 0 {
+            base_element_size * elements_per_leaf
+        } else {
+            ext_element_size * elements_per_leaf
+        };
+        let leaf_bytes = (8 + leaf_elements + fri_config.salt_bytes()) as f64;
+        let tree_height = log_layer_size - elements_per_leaf.ilog2();
+        //a node is sent when it is an unknown child of a parent with a known child
+        let mut width = tree_height;
+        let auth_nodes: f64 = fri_config
+            .commit_config
+            .level_arities(1 << tree_height)
+            .into_iter()
+            .map(|arity| {
+                let parent_width = width - arity.ilog2();
+                let nodes = arity as f64 * expected_known_nodes(parent_width, log_m, q)
+                    - expected_known_nodes(width, log_m, q);
+                width = parent_width;
+                nodes
+            })
+            .sum();
+        //layer commitment
+        bytes += hash_bytes as f64
+            + q * leafs_per_query as f64 * leaf_bytes
+            + auth_nodes * hash_bytes as f64;
+        log_layer_size -= fri_config.log_folding_factor();
+    }
+    //DEEP mode adds one out of domain value per round
+    let num_deep_values = match fri_config.fri_mode {
+        FriMode::Plain => 0,
+        FriMode::Deep => num_rounds as usize,
+    };
+    bytes.round() as usize
+        + (fri_config.final_poly_len() + num_deep_values) * ext_element_size
+        + std::mem::size_of::<u64>()
+}
+
+// digests a tree over `num_leaves` keeps, the leaf digests are level 0 and the levels below
+// `store_min_layer` are dropped after the build
+fn tree_bytes(
+    commit_config: &CommitConfig,
+    num_leaves: usize,
+    hash_bytes: usize,
+    store_min_layer: u64,
+) -> usize {
+    let mut nodes: usize = num_leaves;
+    let mut bytes: usize = if store_min_layer == 0 { nodes * hash_bytes } else { 0 };
+    for (level, arity) in commit_config.level_arities(num_leaves).into_iter().enumerate() {
+        nodes /= arity;
+        if level as u64 + 1 >= store_min_layer {
+            bytes += nodes * hash_bytes;
+        }
+    }
+    bytes
+}
+
+/// Estimated peak memory in bytes of `FriProver::prove` with `memory` on 2^log_domain_size
+/// evaluations: the cached twiddles, what is kept from the commit phase for the query phase
+/// (layers and merkle trees) and the largest transient buffers. An upper estimate, it adds the
+/// transients to everything kept at the end of the commit phase.
+/// Full: twiddles n*e, the input n*b and its leaf order copy (and the mask in zk mode), every
+/// folded layer n_r*e, full trees, and the lifted input with the vec op fold buffers, 3n*e.
+/// Low: twiddles n*b, the first layer in leaf order n*b, trees without their `tree_min_layer`
+/// lowest levels, and two folded layers (n/2 + n/4)*e plus a leaf order copy of a folded
+/// layer for coset leafs. At 2^26 the full trees of single element leafs alone are ~2n*hash_bytes.
+pub fn prover_peak_memory<F, E>(
+    fri_config: &Friconfig,
+    log_domain_size: u32,
+    hash_bytes: usize,
+    memory: ProverMemory,
+) -> usize
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    let base_element_size = F::one().to_bytes_le().len();
+    let ext_element_size = E::one().to_bytes_le().len();
+    let elements_per_leaf = fri_config.elements_per_leaf();
+    let n: usize = 1 << log_domain_size;
+    let num_rounds = fri_config.num_rounds(n) as usize;
+    let log_k = fri_config.log_folding_factor() as usize;
+    let tree_min_layer: u64 = match memory {
+        ProverMemory::Full => 0,
+        ProverMemory::Low { tree_min_layer } => tree_min_layer,
+    };
+    let trees: usize = (0..num_rounds)
+        .map(|r| {
+            tree_bytes(
+                &fri_config.commit_config,
+                (n >> (r * log_k)) / elements_per_leaf,
+                hash_bytes,
+                tree_min_layer,
+            )
+        })
+        .sum();
+    //twiddles of every layer length m hold m/2 elements
+    let twiddles: usize = n - 1;
+    match memory {
+        ProverMemory::Full => {
+            let folded_layers: usize = (1..num_rounds).map(|r| n >> (r * log_k)).sum();
+            //zk: the mask, and salted leaf bytes of every layer
+            let salted: usize = if fri_config.zk {
+                (0..num_rounds)
+                    .map(|r| {
+                        let leaf_elements = if r == 0 {
+                            base_element_size * elements_per_leaf
+                        } else {
+                            ext_element_size * elements_per_leaf
+                        };
+                        let num_leaves = (n >> (r * log_k)) / elements_per_leaf;
+                        num_leaves * (leaf_elements + fri_config.salt_bytes())
+                    })
+                    .sum()
+            } else {
+                0
+            };
+            (twiddles + folded_layers + 3 * n) * ext_element_size
+                + (2 + fri_config.zk as usize) * n * base_element_size
+                + salted
+                + trees
+        }
+        ProverMemory::Low { .. } => {
+            let leaf_copy: usize = match fri_config.leaf_layout {
+                LeafLayout::Single => 0,
+                LeafLayout::Coset => n / fri_config.folding_factor,
+            };
+            //the coset reorder of the input happens before any folded layer exists
+            let transient: usize = match fri_config.leaf_layout {
+                LeafLayout::Single => 0,
+                LeafLayout::Coset => n * base_element_size,
+            }
+            .max((n / 2 + n / 4 + leaf_copy) * ext_element_size);
+            (twiddles + n) * base_element_size + trees + transient
+        }
+    }
+}
+
+impl Friconfig {
+    /// Smallest number of queries whose conjectured query soundness reaches `bits` with
+    /// `blow_up_factor`, after grinding up to 16 bits. The field has to be large enough too,
+    /// see `SecurityReport::conjectured_bits`. Binary folding with coset leafs, on the subgroup.
+    pub fn for_security(bits: usize, blow_up_factor: usize) -> Self {
+        assert!(
+            blow_up_factor.is_power_of_two() && blow_up_factor > 1,
+            "blow up factor should be a power of two larger than one"
+        );
+        let pow_bits = bits.min(16);
+        let log_blowup = blow_up_factor.ilog2() as usize;
+        let num_queries = (bits - pow_bits).div_ceil(log_blowup).max(1);
+        Friconfig {
+            blow_up_factor,
+            folding_factor: 2,
+            pow_bits,
+            pow_hash: HashKind::Blake2s,
+            num_queries,
+            stopping_size: blow_up_factor.max(16),
+            leaf_layout: LeafLayout::Coset,
+            fri_mode: FriMode::Plain,
+            zk: false,
+            domain_offset: 1,
+            commit_config: CommitConfig::default(),
+        }
+    }
+
+    /// Security and proof size of this config for a codeword of 2^log_domain_size.
+    pub fn security_report<F, E>(&self, log_domain_size: u32, hash_bytes: usize) -> SecurityReport
+    where
+        F: Arithmetic + Field + BigNum,
+        E: Arithmetic + Field + BigNum,
+    {
+        SecurityReport::new::<F, E>(self, log_domain_size, hash_bytes)
+    }
+}
@@ -0,0 +1,357 @@
+use icicle_core::{bignum::BigNum, field::Field, traits::Arithmetic};
+use std::marker::PhantomData;
+
+use crate::{data_structures::*, error::FriError};
+
+// Binary layout of a serialized Friproof, all integers little endian
+// header:
+//   magic "FRIP" | version u16 | base field size u32 | base field modulus-1 | ext field size u32
+//   | Friconfig as 5 x u64, leaf layout u8, fri mode u8, zk u8, domain offset u32, pow hash u8
+//     and commit config (leaf hash u8, compression u8, arity u8) | log domain size u32
+// body:
+//   num layer commitments u32, each as bytes
+//   num layer openings u32 (one less than the rounds when the first layer is committed outside
+//   the proof, as in batch and pcs), each:
+//       num leafs u32 | (leaf index u64 | leaf bytes) per leaf | num auth nodes u32 | node bytes per node
+//       (bytes = len u32 || data)
+//   final poly len u32 followed by its coefficients as ext field elements
+//   num deep values u32 followed by the values as ext field elements
+//   pow nonce u64
+pub const FRIPROOF_MAGIC: &[u8; 4] = b"FRIP";
+pub const FRIPROOF_VERSION: u16 = 1;
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn hash_id(hash: HashKind) -> u8 {
+    match hash {
+        HashKind::Blake2s => 0,
+        HashKind::Blake3 => 1,
+        HashKind::Keccak256 => 2,
+        HashKind::Poseidon2 => 3,
+    }
+}
+
+fn hash_kind(id: u8) -> Result<HashKind, FriError> {
+    match id {
+        0 => Ok(HashKind::Blake2s),
+        1 => Ok(HashKind::Blake3),
+        2 => Ok(HashKind::Keccak256),
+        3 => Ok(HashKind::Poseidon2),
+        _ => Err(FriError::MalformedProof("unknown hash")),
+    }
+}
+
+// identifies the field by its size and p-1
+fn field_fingerprint<F: Arithmetic + Field + BigNum>() -> Vec<u8> {
+    (F::zero() - F::one()).to_bytes_le()
+}
+
+// reader over the serialized proof, every read is bounds checked
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        ByteReader { bytes, pos: 0 }
+    }
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FriError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(FriError::MalformedProof("proof bytes truncated"))?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+    fn u8(&mut self) -> Result<u8, FriError> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, FriError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    fn u32(&mut self) -> Result<u32, FriError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> Result<u64, FriError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+    fn usize(&mut self) -> Result<usize, FriError> {
+        usize::try_from(self.u64()?).map_err(|_| FriError::MalformedProof("value overflows usize"))
+    }
+    fn bytes(&mut self) -> Result<&'a [u8], FriError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+    //canonical encoding only, from_bytes_le would silently reduce
+    fn element<F: Arithmetic + Field + BigNum>(&mut self, size: usize) -> Result<F, FriError> {
+        let bytes = self.take(size)?;
+        let element = F::from_bytes_le(bytes);
+        if element.to_bytes_le() != bytes {
+            return Err(FriError::MalformedProof("non canonical field element"));
+        }
+        Ok(element)
+    }
+}
+
+impl Friconfig {
+    /// Canonical encoding of the config, the proof header and the transcript both use it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::new();
+        for param in [
+            self.blow_up_factor,
+            self.folding_factor,
+            self.pow_bits,
+            self.num_queries,
+            self.stopping_size,
+        ] {
+            out.extend_from_slice(&(param as u64).to_le_bytes());
+        }
+        out.push(match self.leaf_layout {
+            LeafLayout::Single => 0,
+            LeafLayout::Coset => 1,
+        });
+        out.push(match self.fri_mode {
+            FriMode::Plain => 0,
+            FriMode::Deep => 1,
+        });
+        out.push(self.zk as u8);
+        out.extend_from_slice(&self.domain_offset.to_le_bytes());
+        out.push(hash_id(self.pow_hash));
+        out.push(hash_id(self.commit_config.leaf_hash));
+        out.push(hash_id(self.commit_config.compression));
+        out.push(self.commit_config.arity as u8);
+        out
+    }
+}
+
+impl<E: Arithmetic + Field + BigNum> Friproof<E> {
+    /// Serialize the proof with a versioned header recording the field, hash, config and domain size.
+    /// `F` is the base field the first layer is committed in. The domain size is passed in, the
+    /// proofs inside batch and pcs openings have no opening of their first layer.
+    pub fn to_bytes<F: Arithmetic + Field + BigNum>(
+        &self,
+        fri_config: &Friconfig,
+        log_domain_size: u32,
+    ) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::new();
+        //header
+        out.extend_from_slice(FRIPROOF_MAGIC);
+        out.extend_from_slice(&FRIPROOF_VERSION.to_le_bytes());
+        put_bytes(&mut out, &field_fingerprint::<F>());
+        out.extend_from_slice(&(E::one().to_bytes_le().len() as u32).to_le_bytes());
+        out.extend_from_slice(&fri_config.to_bytes());
+        out.extend_from_slice(&log_domain_size.to_le_bytes());
+
+        //layer commitments
+        out.extend_from_slice(&(self.layer_commitments.len() as u32).to_le_bytes());
+        for commitment in self.layer_commitments.iter() {
+            put_bytes(&mut out, commitment);
+        }
+
+        //layer openings
+        out.extend_from_slice(&(self.layer_openings.len() as u32).to_le_bytes());
+        for opening in self.layer_openings.iter() {
+            out.extend_from_slice(&(opening.leaf_indices.len() as u32).to_le_bytes());
+            for (leaf_index, leaf) in opening.leaf_indices.iter().zip(opening.leaves.iter()) {
+                out.extend_from_slice(&leaf_index.to_le_bytes());
+                put_bytes(&mut out, leaf);
+            }
+            out.extend_from_slice(&(opening.auth_nodes.len() as u32).to_le_bytes());
+            for node in opening.auth_nodes.iter() {
+                put_bytes(&mut out, node);
+            }
+        }
+
+        //final poly, deep values and nonce
+        for values in [&self.final_poly, &self.deep_values] {
+            out.extend_from_slice(&(values.len() as u32).to_le_bytes());
+            for value in values.iter() {
+                out.extend_from_slice(&value.to_bytes_le());
+            }
+        }
+        out.extend_from_slice(&self.pow_nonce.to_le_bytes());
+        out
+    }
+
+    /// Parse a proof written by `to_bytes`, the batch openings carry all the path data
+    /// so the verifier never needs the prover side `MerkleTree`.
+    /// Returns the config and log domain size recorded in the header with the proof, callers
+    /// should still verify against their own config.
+    pub fn from_bytes<F: Arithmetic + Field + BigNum>(
+        bytes: &[u8],
+    ) -> Result<(Friconfig, u32, Self), FriError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(4)? != FRIPROOF_MAGIC {
+            return Err(FriError::MalformedProof("not a serialized fri proof"));
+        }
+        if reader.u16()? != FRIPROOF_VERSION {
+            return Err(FriError::MalformedProof("unsupported proof version"));
+        }
+        if reader.bytes()? != field_fingerprint::<F>().as_slice() {
+            return Err(FriError::MalformedProof("proof is for a different base field"));
+        }
+        let ext_size = E::one().to_bytes_le().len();
+        if reader.u32()? as usize != ext_size {
+            return Err(FriError::MalformedProof("proof is for a different extension field"));
+        }
+        let fri_config = Friconfig {
+            blow_up_factor: reader.usize()?,
+            folding_factor: reader.usize()?,
+            pow_bits: reader.usize()?,
+            num_queries: reader.usize()?,
+            stopping_size: reader.usize()?,
+            leaf_layout: match reader.u8()? {
+                0 => LeafLayout::Single,
+                1 => LeafLayout::Coset,
+                _ => return Err(FriError::MalformedProof("unknown leaf layout")),
+            },
+            fri_mode: match reader.u8()? {
+                0 => FriMode::Plain,
+                1 => FriMode::Deep,
+                _ => return Err(FriError::MalformedProof("unknown fri mode")),
+            },
+            zk: match reader.u8()? {
+                0 => false,
+                1 => true,
+                _ => return Err(FriError::MalformedProof("unknown zk flag")),
+            },
+            domain_offset: reader.u32()?,
+            pow_hash: hash_kind(reader.u8()?)?,
+            commit_config: CommitConfig {
+                leaf_hash: hash_kind(reader.u8()?)?,
+                compression: hash_kind(reader.u8()?)?,
+                arity: reader.u8()? as usize,
+            },
+        };
+        if !fri_config.folding_factor.is_power_of_two()
+            || fri_config.folding_factor < 2
+            || !fri_config.stopping_size.is_power_of_two()
+            || fri_config.domain_offset == 0
+            || !fri_config.commit_config.is_valid()
+        {
+            return Err(FriError::MalformedProof("invalid fri config"));
+        }
+        let log_domain_size = reader.u32()?;
+
+        let num_commitments = reader.u32()?;
+        let mut layer_commitments: Vec<Vec<u8>> = Vec::new();
+        for _ in 0..num_commitments {
+            layer_commitments.push(reader.bytes()?.to_vec());
+        }
+
+        let num_rounds = reader.u32()?;
+        //all rounds, or all but a first layer committed outside the proof
+        let log_stopping_size = fri_config.stopping_size.ilog2();
+        let log_folding_factor = fri_config.log_folding_factor();
+        if log_domain_size < log_stopping_size
+            || (log_domain_size - log_stopping_size) % log_folding_factor != 0
+        {
+            return Err(FriError::MalformedProof("domain size does not match proof shape"));
+        }
+        let all_rounds = (log_domain_size - log_stopping_size) / log_folding_factor;
+        if num_rounds != all_rounds && num_rounds.checked_add(1) != Some(all_rounds) {
+            return Err(FriError::MalformedProof("domain size does not match proof shape"));
+        }
+        //no preallocation from untrusted lengths, a bad length just runs out of bytes
+        let mut layer_openings: Vec<BatchOpening> = Vec::new();
+        for _ in 0..num_rounds {
+            let mut opening = BatchOpening::default();
+            let num_leafs = reader.u32()?;
+            for _ in 0..num_leafs {
+                opening.leaf_indices.push(reader.u64()?);
+                opening.leaves.push(reader.bytes()?.to_vec());
+            }
+            let num_nodes = reader.u32()?;
+            for _ in 0..num_nodes {
+                opening.auth_nodes.push(reader.bytes()?.to_vec());
+            }
+            layer_openings.push(opening);
+        }
+
+        let final_poly_len = reader.u32()? as usize;
+        let mut final_poly: Vec<E> = Vec::new();
+        for _ in 0..final_poly_len {
+            final_poly.push(reader.element::<E>(ext_size)?);
+        }
+        let num_deep_values = reader.u32()? as usize;
+        let mut deep_values: Vec<E> = Vec::new();
+        for _ in 0..num_deep_values {
+            deep_values.push(reader.element::<E>(ext_size)?);
+        }
+        let pow_nonce = reader.u64()?;
+        if reader.pos != bytes.len() {
+            return Err(FriError::MalformedProof("trailing bytes after proof"));
+        }
+        Ok((
+            fri_config,
+            log_domain_size,
+            Friproof {
+                layer_commitments,
+                layer_openings,
+                final_poly,
+                deep_values,
+                pow_nonce,
+            },
+        ))
+    }
+}
+
+/// A proof together with the config and domain size it was produced for, base field `F`,
+/// extension `E`. With the `serde` feature it (de)serializes as the `to_bytes` encoding.
+pub struct FriproofBundle<F, E> {
+    pub fri_config: Friconfig,
+    pub log_domain_size: u32,
+    pub friproof: Friproof<E>,
+    _base_field: PhantomData<F>,
+}
+
+impl<F, E> FriproofBundle<F, E>
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    pub fn new(fri_config: Friconfig, log_domain_size: u32, friproof: Friproof<E>) -> Self {
+        FriproofBundle {
+            fri_config,
+            log_domain_size,
+            friproof,
+            _base_field: PhantomData,
+        }
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.friproof.to_bytes::<F>(&self.fri_config, self.log_domain_size)
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FriError> {
+        let (fri_config, log_domain_size, friproof) = Friproof::<E>::from_bytes::<F>(bytes)?;
+        Ok(Self::new(fri_config, log_domain_size, friproof))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<F, E> serde::Serialize for FriproofBundle<F, E>
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, F, E> serde::Deserialize<'de> for FriproofBundle<F, E>
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes: Vec<u8> = serde::Deserialize::deserialize(deserializer)?;
+        Self::from_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
@@ -3,17 +3,28 @@ use icicle_core::bignum::BigNum;
 use icicle_core::field::Field;
 use merlin::Transcript;
 
+use crate::data_structures::Friconfig;
+
 pub trait TranscriptProtocol<F: Arithmetic + Field +BigNum> {
     fn fri_domain_sep(
         &mut self,
-        domain_seperator: &'static [u8],
+        domain_seperator: &[u8],
         init_domain_size: u64,
         public: Vec<u8>,
     );
+    /// Bind the proof to its parameters: the encoded `Friconfig` and the degree bound
+    /// (number of coefficients) of the committed codeword.
+    fn append_config(&mut self, fri_config: &Friconfig, degree_bound: u64);
+    /// Number of columns of a batched proof and the codeword length and degree bound of each.
+    fn append_columns(&mut self, column_sizes: &[usize], degree_bounds: &[usize]);
     /// Append a `scalar` with the given `label`.
     fn append_root(&mut self, label: &'static [u8], scalar: &F);
+    /// Append the full bytes of a layer commitment (merkle root) with the given `label`.
+    fn append_commitment(&mut self, label: &'static [u8], commitment: &[u8]);
     /// Compute a `label`ed challenge variable.
     fn challenge_scalar(&mut self, label: &'static [u8]) -> F;
+    /// Uniform `label`ed index in [0, max), rejection sampled from 8 squeezed bytes.
+    fn challenge_index(&mut self, label: &'static [u8], max: usize) -> usize;
     // add pow nonce
     fn add_nonce(&mut self, nonce: u64);
 }
@@ -21,7 +32,7 @@ pub trait TranscriptProtocol<F: Arithmetic + Field +BigNum> {
 impl<F: Arithmetic + Field +BigNum> TranscriptProtocol<F> for Transcript {
     fn fri_domain_sep(
         &mut self,
-        domain_seperator: &'static [u8],
+        domain_seperator: &[u8],
         init_domain_size: u64,
         public: Vec<u8>,
     ) {
@@ -30,15 +41,47 @@ impl<F: Arithmetic + Field +BigNum> TranscriptProtocol<F> for Transcript {
         self.append_message(b"public", &public);
     }
 
+    fn append_config(&mut self, fri_config: &Friconfig, degree_bound: u64) {
+        self.append_message(b"fri_config", &fri_config.to_bytes());
+        self.append_u64(b"degree_bound", degree_bound);
+    }
+
+    fn append_columns(&mut self, column_sizes: &[usize], degree_bounds: &[usize]) {
+        self.append_u64(b"num_columns", degree_bounds.len() as u64);
+        for (&column_size, &degree_bound) in column_sizes.iter().zip(degree_bounds.iter()) {
+            self.append_u64(b"column_size", column_size as u64);
+            self.append_u64(b"column_degree_bound", degree_bound as u64);
+        }
+    }
+
     fn append_root(&mut self, label: &'static [u8], scalar: &F) {
         self.append_message(label, &scalar.to_bytes_le());
     }
 
+    fn append_commitment(&mut self, label: &'static [u8], commitment: &[u8]) {
+        self.append_message(label, commitment);
+    }
+
     fn challenge_scalar(&mut self, label: &'static [u8]) -> F {
         let mut buf = [0u8; 64];
         self.challenge_bytes(label, &mut buf);
         F::from_bytes_le(&buf)
     }
+
+    fn challenge_index(&mut self, label: &'static [u8], max: usize) -> usize {
+        assert!(max > 0, "empty index range");
+        let max = max as u64;
+        //largest multiple of max that fits in a u64, draws above it would bias the low indices
+        let zone = u64::MAX - (u64::MAX - max + 1) % max;
+        loop {
+            let mut buf = [0u8; 8];
+            self.challenge_bytes(label, &mut buf);
+            let draw = u64::from_le_bytes(buf);
+            if draw <= zone {
+                return (draw % max) as usize;
+            }
+        }
+    }
     fn add_nonce(&mut self, nonce: u64) {
         self.append_u64(b"pow_nonce", nonce);
     }
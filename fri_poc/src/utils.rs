@@ -1,16 +1,17 @@
+use std::collections::HashSet;
+
 use icicle_core::{
-    bignum::BigNum, hash::HashConfig, ntt::{
+    bignum::BigNum, hash::{HashConfig, Hasher}, ntt::{
         get_root_of_unity, initialize_domain, ntt, NTTConfig, NTTDir, NTTDomain,
         NTTInitDomainConfig, NTT,
     }, polynomials::UnivariatePolynomial, ring::IntegerRing, traits::{Arithmetic, GenerateRandom, Invertible}
 };
-use icicle_hash::blake2s::Blake2s;
+
+use crate::{data_structures::HashKind, pow::grind, transcript::TranscriptProtocol};
+use merlin::Transcript;
 use icicle_runtime::{memory::HostSlice, runtime, Device};
 use icicle_core::field::Field;
 
-use rand::{distr::Uniform, Rng, SeedableRng};
-use rand_chacha::ChaCha20Rng;
-
 pub fn set_backend_cpu() {
     // icicle_runtime::load_backend("../Polynomial-API/cuda_backend/icicle/lib/backend").unwrap();
     let device_cpu = Device::new("CPU", 0);
@@ -77,15 +78,40 @@ where
     initialize_domain(rou, &NTTInitDomainConfig::default()).unwrap();
 }
 
-pub fn coeff_to_eval_blowup<F: Arithmetic+Field+BigNum+NTTDomain<F>+NTT<F, F>>(mut input: Vec<F>, size: usize) -> Vec<F>
+/// Inverse twiddles of every subgroup up to 2^max_log_size: entry log m is
+/// (1, w_m^{-1}, .., w_m^{-(m/2-1)}), entry 0 is empty. Each table is every other element of the
+/// one above it, about 2^max_log_size elements in total.
+pub fn inverse_twiddles<F>(max_log_size: u32) -> Vec<Vec<F>>
+where
+    F: Arithmetic+Field+BigNum+NTTDomain<F>,
+{
+    let size: usize = 1 << max_log_size;
+    let rou_inv: F = get_root_of_unity::<F>(size as u64).unwrap().inv();
+    let mut current = F::one();
+    let mut top: Vec<F> = Vec::with_capacity(size / 2);
+    for _ in 0..size / 2 {
+        top.push(current);
+        current = current * rou_inv;
+    }
+    let mut twiddles: Vec<Vec<F>> = vec![top];
+    for _ in 1..max_log_size {
+        let smaller: Vec<F> = twiddles.last().unwrap().iter().step_by(2).cloned().collect();
+        twiddles.push(smaller);
+    }
+    twiddles.push(Vec::new());
+    twiddles.reverse();
+    twiddles
+}
 
+/// LDE on offset*H like `coeff_to_eval_blowup_coset`, for an NTT domain that is already
+/// initialized for at least `size`.
+pub fn coset_lde<F: Arithmetic+Field+BigNum+NTT<F, F>>(mut input: Vec<F>, size: usize, offset: F) -> Vec<F>
 {
+    assert!(input.len() <= size, "more coefficients than the evaluation domain");
     //zero pad coeffs to dest size
-    let mut vz: Vec<F> = vec![F::zero(); size - input.len()];
-    input.append(&mut vz);
-    let logsize = size.ilog2();
-    init_ntt_domain::<F>(1 << logsize);
-    let ntt_cfg: NTTConfig<F> = NTTConfig::<F>::default();
+    input.resize(size, F::zero());
+    let mut ntt_cfg: NTTConfig<F> = NTTConfig::<F>::default();
+    ntt_cfg.coset_gen = offset;
     let mut poly_eval = vec![F::zero(); size];
     ntt(
         HostSlice::from_slice(&input),
@@ -97,8 +123,30 @@ pub fn coeff_to_eval_blowup<F: Arithmetic+Field+BigNum+NTTDomain<F>+NTT<F, F>>(m
     poly_eval
 }
 
+pub fn coeff_to_eval_blowup<F: Arithmetic+Field+BigNum+NTTDomain<F>+NTT<F, F>>(input: Vec<F>, size: usize) -> Vec<F>
+
+{
+    coeff_to_eval_blowup_coset(input, size, F::one())
+}
+
+/// LDE of the coefficients on the coset offset*H, |H| = size. offset = one gives the subgroup.
+pub fn coeff_to_eval_blowup_coset<F: Arithmetic+Field+BigNum+NTTDomain<F>+NTT<F, F>>(input: Vec<F>, size: usize, offset: F) -> Vec<F>
+
+{
+    let logsize = size.ilog2();
+    init_ntt_domain::<F>(1 << logsize);
+    coset_lde(input, size, offset)
+}
+
 pub fn eval_to_eval_blowup<F: Arithmetic+Field+BigNum+NTTDomain<F>+NTT<F, F>>(input: Vec<F>, size: usize) -> Vec<F>
 
+{
+    eval_to_eval_blowup_coset(input, size, F::one())
+}
+
+/// Evals on the subgroup of size input.len() to evals on offset*H, |H| = size.
+pub fn eval_to_eval_blowup_coset<F: Arithmetic+Field+BigNum+NTTDomain<F>+NTT<F, F>>(input: Vec<F>, size: usize, offset: F) -> Vec<F>
+
 {
     let logsize = input.len().ilog2();
     init_ntt_domain::<F>(1 << logsize);
@@ -111,7 +159,7 @@ pub fn eval_to_eval_blowup<F: Arithmetic+Field+BigNum+NTTDomain<F>+NTT<F, F>>(in
         HostSlice::from_mut_slice(&mut poly_coeff[..]),
     )
     .unwrap();
-    coeff_to_eval_blowup(poly_coeff, size)
+    coeff_to_eval_blowup_coset(poly_coeff, size, offset)
 }
 
 pub fn num_leading_zeros(bytes: Vec<u8>) -> usize {
@@ -127,56 +175,173 @@ pub fn num_leading_zeros(bytes: Vec<u8>) -> usize {
     leading_zeros
 }
 
-pub fn hash_fuse(a: Vec<u8>, b: Vec<u8>) -> Vec<u8> {
-    let leaf_size: u64 = 4; //for 32 bit fields
-    let hasher = Blake2s::new(leaf_size).unwrap();
-    let mut fused_bytes: Vec<u8> = Vec::with_capacity(a.len() + b.len());
-    fused_bytes.extend_from_slice(&a);
-    fused_bytes.extend_from_slice(&b);
+/// Hash `input` with a single hasher call, `None` if icicle rejects the input size.
+pub fn hash_bytes(hasher: &Hasher, input: &[u8]) -> Option<Vec<u8>> {
     let cfg = HashConfig::default();
-    let mut output: Vec<u8> = vec![0u8; 32];
+    let mut output: Vec<u8> = vec![0u8; hasher.output_size() as usize];
     hasher
         .hash(
-            HostSlice::from_slice(&fused_bytes),
+            HostSlice::from_slice(input),
             &cfg,
             HostSlice::from_mut_slice(&mut output),
         )
-        .unwrap();
-    output
+        .ok()?;
+    Some(output)
 }
 
+/// Blake2s proof of work on a field challenge, see `pow::grind` for the other hashes.
 pub fn proof_of_work<F>(pow_bits: usize, transcript_challenge: F) -> u64
 where
     F: Arithmetic+Field+BigNum,
 {
-    (0u64..u64::MAX) // Create a parallel iterator, how to run this using batch mode of the hash used?
-        .find(|&nonce| {
-            //goal is to find nonce such that H(challenge||nonce) =digest such that digest has pow_bits leading zeros
-            let output = hash_fuse(
-                transcript_challenge.to_bytes_le(),
-                nonce.to_le_bytes().to_vec(),
-            );
-            // Count leading zeros in the bit representation of the nonce
-            let leading_zeros: usize = num_leading_zeros(output);
-
-            // Check if the nonce meets the condition
-            leading_zeros == pow_bits
-        })
-        .expect("No nonce found") // Safeguard: this should never trigger unless overflow
+    grind(HashKind::Blake2s, pow_bits, &transcript_challenge.to_bytes_le())
+}
+
+/// `num_queries` distinct query indices in [0, max), squeezed from the transcript in draw order.
+/// Repeated draws are dropped and drawing continues, the number of dropped draws is returned too.
+pub fn sample_distinct_queries<F>(
+    transcript: &mut Transcript,
+    num_queries: usize,
+    max: usize,
+) -> (Vec<usize>, usize)
+where
+    F: Arithmetic+Field+BigNum,
+{
+    assert!(num_queries <= max, "more distinct queries than positions");
+    //only the drawn indices are tracked, max is n/k and can be 2^25
+    let mut seen: HashSet<usize> = HashSet::with_capacity(num_queries);
+    let mut query_indices: Vec<usize> = Vec::with_capacity(num_queries);
+    let mut duplicates: usize = 0;
+    while query_indices.len() < num_queries {
+        let index = <Transcript as TranscriptProtocol<F>>::challenge_index(transcript, b"query", max);
+        if seen.insert(index) {
+            query_indices.push(index);
+        } else {
+            duplicates += 1;
+        }
+    }
+    (query_indices, duplicates)
 }
 
-pub fn generate_samples_in_range(seed_bytes: Vec<u8>, size: usize, max: usize) -> Vec<usize> {
-    let mut seed = [0u8; 32];
-    let len = seed_bytes.len().min(32);
-    seed[..len].copy_from_slice(&seed_bytes[..len]);
+/// Embed a base field element into the extension field as the constant coefficient.
+/// Relies on icicle extension elements being laid out as little endian base field limbs.
+pub fn lift<F, E>(x: &F) -> E
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    let mut bytes = x.to_bytes_le();
+    bytes.resize(E::one().to_bytes_le().len(), 0u8);
+    E::from_bytes_le(&bytes)
+}
 
-    let mut rng = ChaCha20Rng::from_seed(seed);
+pub fn lift_vec<F, E>(input: &[F]) -> Vec<E>
+where
+    F: Arithmetic + Field + BigNum,
+    E: Arithmetic + Field + BigNum,
+{
+    input.iter().map(lift::<F, E>).collect()
+}
 
-    (0..size)
-        .map(|_| rng.sample(Uniform::new(0, max).expect("error")))
+/// Lagrange interpolate the points (xs[i], ys[i]) and evaluate the interpolant at z.
+/// Used by the verifier to fold a k-coset, quadratic in k which is fine for k<=16.
+pub fn interpolate_and_evaluate<F>(xs: &[F], ys: &[F], z: F) -> F
+where
+    F: Arithmetic + Field + BigNum,
+{
+    let mut result = F::zero();
+    for (i, (&x_i, &y_i)) in xs.iter().zip(ys.iter()).enumerate() {
+        let mut num = F::one();
+        let mut den = F::one();
+        for (j, &x_j) in xs.iter().enumerate() {
+            if i != j {
+                num = num * (z - x_j);
+                den = den * (x_i - x_j);
+            }
+        }
+        result = result + y_i * num * den.inv();
+    }
+    result
+}
+
+/// Coefficients of the poly whose evals on the coset offset*H are `evals`, |H| = evals.len().
+/// Inverse DFT on host, c_j = offset^{-j}/n sum_i evals[i] w^{-ij}. Quadratic in the length,
+/// only meant for the small final layer.
+pub fn interpolate_coset<F, E>(evals: &[E], offset_inv: E) -> Vec<E>
+where
+    F: Arithmetic + Field + BigNum + NTTDomain<F>,
+    E: Arithmetic + Field + BigNum,
+{
+    let n = evals.len();
+    let rou_inv: E = lift::<F, E>(&get_root_of_unity::<F>(n as u64).unwrap().inv());
+    let mut coeffs: Vec<E> = Vec::with_capacity(n);
+    //w^{-j} and offset^{-j}/n
+    let mut rou_inv_j = E::one();
+    let mut scale = E::from_u32(n as u32).inv();
+    for _ in 0..n {
+        let mut acc = E::zero();
+        let mut x = E::one();
+        for &eval in evals.iter() {
+            acc = acc + eval * x;
+            x = x * rou_inv_j;
+        }
+        coeffs.push(acc * scale);
+        rou_inv_j = rou_inv_j * rou_inv;
+        scale = scale * offset_inv;
+    }
+    coeffs
+}
+
+/// Value at `point` of the poly whose evals on the coset offset*H are `evals`, without
+/// interpolating. Barycentric form, with x_i = offset*w^i and n = |H|:
+/// p(z) = (z^n - offset^n)/(n offset^n) sum_i evals[i] x_i/(z - x_i). `point` should not be in offset*H.
+pub fn evaluate_coset<F, E>(evals: &[E], offset: E, point: E) -> E
+where
+    F: Arithmetic + Field + BigNum + NTTDomain<F>,
+    E: Arithmetic + Field + BigNum,
+{
+    let n = evals.len();
+    let rou: E = lift::<F, E>(&get_root_of_unity::<F>(n as u64).unwrap());
+    let mut x = offset;
+    let mut acc = E::zero();
+    for &eval in evals.iter() {
+        acc = acc + eval * x * (point - x).inv();
+        x = x * rou;
+    }
+    let offset_n = pow(offset, n as u32);
+    acc * (pow(point, n as u32) - offset_n) * (E::from_u32(n as u32) * offset_n).inv()
+}
+
+/// Evals of the DEEP quotient (p(X) - value)/(X - point) on offset*H from the evals of p there.
+pub fn deep_quotient<F, E>(evals: &[E], offset: E, point: E, value: E) -> Vec<E>
+where
+    F: Arithmetic + Field + BigNum + NTTDomain<F>,
+    E: Arithmetic + Field + BigNum,
+{
+    let rou: E = lift::<F, E>(&get_root_of_unity::<F>(evals.len() as u64).unwrap());
+    let mut x = offset;
+    evals
+        .iter()
+        .map(|&eval| {
+            let denominator = point - x;
+            assert!(denominator != E::zero(), "deep point in the evaluation domain");
+            x = x * rou;
+            (value - eval) * denominator.inv()
+        })
         .collect()
 }
 
+/// Horner evaluation of the coefficients (low degree first) at x.
+pub fn evaluate_poly<F>(coeffs: &[F], x: F) -> F
+where
+    F: Arithmetic + Field + BigNum,
+{
+    coeffs
+        .iter()
+        .rev()
+        .fold(F::zero(), |acc, &coeff| acc * x + coeff)
+}
+
 pub fn pow<F>(mut base: F, mut exp: u32) -> F
 where
     F: Arithmetic+Field+BigNum,
@@ -3,210 +3,519 @@ use icicle_core::{
 };
 
 use crate::utils::*;
-use crate::{data_structures::*, transcript::*};
-use icicle_hash::blake2s::Blake2s;
+use crate::{data_structures::*, error::FriError, pow::verify_pow, transcript::*};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter,
+};
 use log::debug; 
 use merlin::Transcript;
 use icicle_core::field::Field;
 
 //methods for verifier
-impl<F: Arithmetic+Field+BigNum> Friproof<F> {
-    //checks the path of the query proof
-    pub fn verify_path(
-        &mut self,
-        layer_index: usize,
-        leaf_index: usize,
-        tree_height: usize,
+impl BatchOpening {
+    /// Recompute the root of the `commit_config` tree over `num_leaves` leafs from the opened
+    /// leaves and the deduplicated authentication nodes and compare it with `root`. Every node
+    /// has to be consumed, and the same index opened twice has to carry the same leaf.
+    pub fn verify(
+        &self,
+        root: &[u8],
+        commit_config: &CommitConfig,
+        num_leaves: usize,
+        leaf_size: u64,
     ) -> bool {
-        //to replace this with generics and merkle config
-        let leaf_size: u64 = (F::one()).to_bytes_le().len().try_into().unwrap(); //4 for 32 bit fields
-        let hasher = Blake2s::new(leaf_size).unwrap();
-        //binary tree
-        let compress = Blake2s::new(hasher.output_size() * 2).unwrap();
-        let layer_hashes: Vec<&Hasher> = std::iter::once(&hasher)
-            .chain(std::iter::repeat(&compress).take(tree_height))
-            .collect();
-        let verifier_tree = MerkleTree::new(&layer_hashes, leaf_size, 0).unwrap();
-        let result = verifier_tree.verify(&self.query_proofs[layer_index][leaf_index]);
-        debug!("result of tree verification {:?}", result);
-        match verifier_tree.verify(&self.query_proofs[layer_index][leaf_index]) {
-            Ok(true) => true, // Verification succeeded
-            Ok(false) => {
-                eprintln!(
-                    "Verification failed: Fake path detected at layer {:?} and leaf {:?}",
-                    layer_index, leaf_index
-                );
-                false
+        self.authenticate(root, commit_config, num_leaves, leaf_size).is_ok()
+    }
+
+    /// `verify` with the index of the first leaf that fails. A leaf of the wrong size, out of
+    /// range or opened twice with different bytes is reported itself. The paths share their
+    /// nodes, so a wrong root or authentication node fails every leaf and the first opened one
+    /// is reported.
+    pub fn authenticate(
+        &self,
+        root: &[u8],
+        commit_config: &CommitConfig,
+        num_leaves: usize,
+        leaf_size: u64,
+    ) -> Result<(), u64> {
+        let first_leaf: u64 = self.leaf_indices.first().copied().unwrap_or(0);
+        if !commit_config.is_valid() || !num_leaves.is_power_of_two() {
+            return Err(first_leaf);
+        }
+        //leaf hasher, then one compression per level
+        let Some(hashers) = commit_config.layer_hashers(leaf_size, num_leaves) else {
+            return Err(first_leaf);
+        };
+        let digest_size = hashers[0].output_size() as usize;
+        if self.leaves.len() != self.leaf_indices.len() || self.leaves.is_empty() {
+            return Err(first_leaf);
+        }
+        //current level of the tree, node index -> digest
+        let mut known: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+        for (&index, leaf) in self.leaf_indices.iter().zip(self.leaves.iter()) {
+            if leaf.len() as u64 != leaf_size || index >= num_leaves as u64 {
+                return Err(index);
             }
-            Err(err) => {
-                eprintln!(
-                    "Error during verification at layer {:?} and leaf {:?}: {:?}",
-                    layer_index, leaf_index, err
-                );
-                false
+            let Some(digest) = hash_bytes(&hashers[0], leaf) else {
+                return Err(index);
+            };
+            match known.get(&index) {
+                Some(existing) if *existing != digest => {
+                    debug!("Verification failed: index {:?} opened with two leafs", index);
+                    return Err(index);
+                }
+                _ => {
+                    known.insert(index, digest);
+                }
+            }
+        }
+        let mut auth_nodes = self.auth_nodes.iter();
+        let arities = commit_config.level_arities(num_leaves);
+        for (compress, &arity) in hashers[1..].iter().zip(arities.iter()) {
+            let arity = arity as u64;
+            let mut parents: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+            let parent_indices: BTreeSet<u64> = known.keys().map(|index| index / arity).collect();
+            for parent in parent_indices {
+                //children in index order, the unknown ones come from the auth nodes
+                let mut input: Vec<u8> = Vec::with_capacity(arity as usize * digest_size);
+                for child in parent * arity..(parent + 1) * arity {
+                    let node = match known.get(&child) {
+                        Some(digest) => digest,
+                        None => match auth_nodes.next() {
+                            Some(node) if node.len() == digest_size => node,
+                            _ => return Err(first_leaf),
+                        },
+                    };
+                    input.extend_from_slice(node);
+                }
+                let Some(digest) = hash_bytes(compress, &input) else {
+                    return Err(first_leaf);
+                };
+                parents.insert(parent, digest);
             }
+            known = parents;
+        }
+        let root_matches = known.get(&0).is_some_and(|computed| computed.as_slice() == root);
+        if !root_matches {
+            debug!("Verification failed: Fake path detected, root mismatch");
+        }
+        if root_matches && auth_nodes.next().is_none() {
+            Ok(())
+        } else {
+            Err(first_leaf)
         }
     }
 }
 
-pub fn verify<F>(
+pub fn verify<F, E>(
     fri_config: Friconfig,
-    mut friproof: Friproof<F>,
+    log_domain_size: u32, //claimed degree bound is 2^log_domain_size / blow_up_factor
+    domain_separator: &[u8], //same protocol label and public input as the prover
+    public_input: &[u8],
+    friproof: Friproof<E>,
     transcript: &mut Transcript,
-) -> Result<bool, &'static str>
+) -> Result<(), FriError>
 where
     F: Arithmetic + BigNum + Field + VecOps<F> + NTTDomain<F> + IntegerRing,
+    E: Arithmetic + BigNum + Field,
 {
-    debug!("query_proof_len {:?}", friproof.query_proofs.len());
-    let exp: usize = friproof.query_proofs.len() / fri_config.num_queries;
-    let exp_stop: usize = fri_config.stopping_size.ilog2().try_into().unwrap();
-    let size: usize = 1 << (exp + exp_stop);
-    let num_rounds = size.ilog2() - fri_config.stopping_size.ilog2();
+    FriVerifier::<F>::new(fri_config, log_domain_size).verify(
+        log_domain_size,
+        domain_separator,
+        public_input,
+        friproof,
+        transcript,
+    )
+}
+
+/// Shape checks, everything after them indexes into the proof based on these. Returns the
+/// domain size. `first_round` is 1 when the first layer is committed outside of `friproof`.
+pub(crate) fn check_shape<E>(
+    fri_config: &Friconfig,
+    log_domain_size: u32,
+    friproof: &Friproof<E>,
+    first_round: usize,
+) -> Result<usize, FriError> {
+    debug!("layer_openings_len {:?}", friproof.layer_openings.len());
+    if fri_config.num_queries == 0
+        || fri_config.domain_offset == 0
+        || !fri_config.commit_config.is_valid()
+        || !fri_config.folding_factor.is_power_of_two()
+        || fri_config.folding_factor < 2
+        || !fri_config.stopping_size.is_power_of_two()
+        || !fri_config.blow_up_factor.is_power_of_two()
+        || fri_config.blow_up_factor > fri_config.stopping_size
+    {
+        return Err(FriError::MalformedProof("invalid fri config"));
+    }
+    //the zk mask is committed with the first layer, which the caller commits in batch and pcs
+    if fri_config.zk && first_round != 0 {
+        return Err(FriError::MalformedProof("zk mode needs the first layer in the proof"));
+    }
+    //the domain size is the verifier's, the proof has to have the matching number of layers
+    let log_stop: u32 = fri_config.stopping_size.ilog2();
+    if log_domain_size >= u32::BITS
+        || log_domain_size <= log_stop
+        || (log_domain_size - log_stop) % fri_config.log_folding_factor() != 0
+    {
+        return Err(FriError::MalformedProof(
+            "domain size should be stopping size times a power of the folding factor",
+        ));
+    }
+    let size: usize = 1 << log_domain_size;
+    let num_rounds = fri_config.num_rounds(size);
+    if friproof.layer_openings.len() + first_round != num_rounds as usize {
+        return Err(FriError::MalformedProof("one layer opening per round expected"));
+    }
+    if friproof.layer_commitments.len() != friproof.layer_openings.len() {
+        return Err(FriError::MalformedProof("one commitment per layer opening expected"));
+    }
+    //every layer opens the folding_factor siblings of each query, in 1 or k leafs
+    let elements_per_leaf = fri_config.elements_per_leaf();
+    let leafs_per_query = fri_config.folding_factor / elements_per_leaf;
+    let leafs_per_layer = fri_config.num_queries * leafs_per_query;
+    if friproof.layer_openings.iter().any(|opening| {
+        opening.leaf_indices.len() != leafs_per_layer || opening.leaves.len() != leafs_per_layer
+    }) {
+        return Err(FriError::MalformedProof(
+            "layer opening does not contain folding_factor leafs per query",
+        ));
+    }
+    if friproof.final_poly.len() > fri_config.final_poly_len() {
+        return Err(FriError::FinalPolyDegree {
+            coeffs: friproof.final_poly.len(),
+            max_coeffs: fri_config.final_poly_len(),
+        });
+    }
+    if friproof.final_poly.len() != fri_config.final_poly_len() {
+        return Err(FriError::MalformedProof(
+            "final poly has fewer coefficients than the degree bound",
+        ));
+    }
+    //DEEP mode sends the value of every fold at its out of domain point
+    let num_deep_values = match fri_config.fri_mode {
+        FriMode::Plain => 0,
+        FriMode::Deep => num_rounds as usize,
+    };
+    if friproof.deep_values.len() != num_deep_values {
+        return Err(FriError::MalformedProof("one deep value per round expected in deep mode"));
+    }
+    //queries are distinct, there have to be enough top layer cosets for them
+    if fri_config.num_queries > size / fri_config.folding_factor {
+        return Err(FriError::MalformedProof("more queries than top layer cosets"));
+    }
+    Ok(size)
+}
 
-    debug!("verifier_size {:?}", size);
+/// Values of the first layer when it is not committed in the proof: called with the top layer
+/// query indices, returns for every query the folding_factor values at index + j*n/k.
+pub(crate) type FirstLayer<'a, E> = &'a dyn Fn(&[usize]) -> Result<Vec<Vec<E>>, FriError>;
 
-    <Transcript as TranscriptProtocol<F>>::fri_domain_sep(
-        transcript,
-        b"my_Shitty_FRI",
-        size.try_into().unwrap(),
-        b"public".to_vec(),
-    );
-
-    //merkle verifyL Proof structure is as follows for folding in 2
-    //[[[proof_0,proof_0sym],[proof_1,proof_1sym],...num_rounds times],....., num_queries times]
-
-    let mut rq: usize = 0;
-    let nr: usize = num_rounds.try_into().unwrap();
-    for q in 0..fri_config.num_queries {
-        let mut start_size = size;
-        for r in 0..nr {
-            let tree_height: usize = start_size.ilog2() as usize;
-            debug!("query no {:?}, tree height verifier {:?}", q, tree_height);
-            for j in 0..fri_config.folding_factor {
-                friproof.verify_path(r + rq, j, tree_height);
-            }
-            //this should work for any folding factor
-            start_size /= fri_config.folding_factor;
+/// Values added to the folded layers, see `FriProver::prove_layers`: called with the top layer
+/// query indices after `FirstLayer`, returns for every query one value per round, entry r is
+/// added at the query's position in layer r (entry 0 is not used).
+pub(crate) type RollIns<'a, E> = &'a dyn Fn(&[usize]) -> Result<Vec<Vec<E>>, FriError>;
+
+/// Verifier for one `Friconfig` and domains of up to 2^max_log_size elements. The roots of unity
+/// of every domain size are looked up once in `new`, the folding checks of every proof then
+/// walk the query points with multiplications only.
+pub struct FriVerifier<F> {
+    pub fri_config: Friconfig,
+    pub max_log_size: u32,
+    rous: Vec<F>, //rous[log n] generates H_n, up to the field's two-adicity
+}
+
+impl<F> FriVerifier<F>
+where
+    F: Arithmetic + BigNum + Field + VecOps<F> + NTTDomain<F> + IntegerRing,
+{
+    pub fn new(fri_config: Friconfig, max_log_size: u32) -> Self {
+        let rous: Vec<F> = (0..=max_log_size.min(u64::BITS - 1))
+            .map_while(|log_size| get_root_of_unity::<F>(1u64 << log_size).ok())
+            .collect();
+        FriVerifier {
+            fri_config,
+            max_log_size,
+            rous,
         }
-        rq += nr;
     }
 
-    let query_proofs: Vec<Vec<MerkleProof>> = friproof.query_proofs;
-    //read roots from proof and gen challenge
-    // we need only one root per layer, our query proofs is structured with many query proofs per query
-    //
-
-    let mut challenge_vec: Vec<F> = Vec::<F>::new();
+    /// Same as `verify` with this verifier's config, `log_domain_size` at most `max_log_size`.
+    pub fn verify<E>(
+        &self,
+        log_domain_size: u32,
+        domain_separator: &[u8],
+        public_input: &[u8],
+        friproof: Friproof<E>,
+        transcript: &mut Transcript,
+    ) -> Result<(), FriError>
+    where
+        E: Arithmetic + BigNum + Field,
+    {
+        let fri_config = self.fri_config;
+        let size = check_shape(&fri_config, log_domain_size, &friproof, 0)?;
+        if log_domain_size > self.max_log_size {
+            return Err(FriError::MalformedProof("domain size above the verifier's max size"));
+        }
+        debug!("verifier_size {:?}", size);
 
-    for query_proof in query_proofs.iter().take(num_rounds.try_into().unwrap()) {
-        let current_root = query_proof[0].get_root::<F>()[0];
-        <Transcript as TranscriptProtocol<F>>::append_root(transcript, b"commit", &current_root);
-        challenge_vec.push(<Transcript as TranscriptProtocol<F>>::challenge_scalar(
+        <Transcript as TranscriptProtocol<F>>::fri_domain_sep(
             transcript,
-            b"challenge",
-        ));
+            domain_separator,
+            size.try_into().unwrap(),
+            public_input.to_vec(),
+        );
+        //everything the proof is checked against goes in before the first commitment
+        <Transcript as TranscriptProtocol<F>>::append_config(
+            transcript,
+            &fri_config,
+            (size / fri_config.blow_up_factor).try_into().unwrap(),
+        );
+
+        self.verify_layers(size, &friproof, transcript, None, None)
     }
 
-    debug!("challenge_vec {:?}", challenge_vec);
-
-    //nonce check
-    let current_challenge: F =
-        <Transcript as TranscriptProtocol<F>>::challenge_scalar(transcript, b"challenge");
-    debug!("POW_challenge {:?}", current_challenge);
-    let nonce: u64 = friproof.pow_nonce;
-    debug!("nonce {:?}", nonce);
-    let out: Vec<u8> = hash_fuse(
-        current_challenge.to_bytes_le(),
-        nonce.to_le_bytes().to_vec(),
-    );
-    let out_lead_zeros: usize = num_leading_zeros(out);
-    assert_eq!(
-        out_lead_zeros, fri_config.pow_bits,
-        "Nonce does not satisfy POW condition"
-    );
-
-    //add nonce to transcript
-    <Transcript as TranscriptProtocol<F>>::add_nonce(transcript, nonce);
-
-    let seed = <Transcript as TranscriptProtocol<F>>::challenge_scalar(transcript, b"sample")
-        .to_bytes_le();
-    debug!("Verifier_seed for sampling based on transcript {:?}", seed);
-    let query_indices: Vec<usize> = generate_samples_in_range(
-        seed,
-        fri_config.num_queries,
-        size / fri_config.folding_factor,
-    );
-    debug!("top layer query_indices {:?}", query_indices);
-
-    let mut leafs: Vec<F> = Vec::<F>::new();
-    let mut leafs_sym: Vec<F> = Vec::<F>::new();
-    let mut indices: Vec<usize> = Vec::<usize>::new();
-    let mut indices_sym: Vec<usize> = Vec::<usize>::new();
-    let mut top_indices: Vec<usize> = Vec::<usize>::new();
-
-    //here we need to collect for each query proofs, the relevant leafs and indices
-
-    let mut rq: usize = 0;
-    let nr: usize = num_rounds.try_into().unwrap();
-    for _ in 0..fri_config.num_queries {
-        for r in 0..nr {
-            let (leaf, index) = query_proofs[r + rq][0].get_leaf::<F>();
-            leafs.push(leaf[0]);
-            indices.push(index.try_into().unwrap());
-            let (leaf_sym, index_sym) = query_proofs[r + rq][1].get_leaf::<F>();
-            leafs_sym.push(leaf_sym[0]);
-            indices_sym.push(index_sym.try_into().unwrap());
+    /// Merkle, pow, query index and folding checks of a proof that passed `check_shape`, once the
+    /// statement is in the transcript. Without `first_layer` every layer is opened in `friproof`.
+    pub(crate) fn verify_layers<E>(
+        &self,
+        size: usize,
+        friproof: &Friproof<E>,
+        transcript: &mut Transcript,
+        first_layer: Option<FirstLayer<E>>,
+        roll_ins: Option<RollIns<E>>,
+    ) -> Result<(), FriError>
+    where
+        E: Arithmetic + BigNum + Field,
+    {
+        let fri_config = self.fri_config;
+        let first_round: usize = if first_layer.is_some() { 1 } else { 0 };
+        let num_rounds = fri_config.num_rounds(size);
+        let elements_per_leaf = fri_config.elements_per_leaf();
+        let leafs_per_query = fri_config.folding_factor / elements_per_leaf;
+
+        //Proof structure is one batch opening per layer, leafs in query order:
+        //[[leaf_q0_0,..,leaf_q0_(k-1), leaf_q1_0,...,num_queries times],....., num_rounds times]
+        //with coset leafs, leaf_q is the whole coset (x_0,..,x_(k-1))
+
+        let base_element_size: usize = (F::one()).to_bytes_le().len(); //4 for 32 bit fields
+        let ext_element_size: usize = (E::one()).to_bytes_le().len(); //16 for degree 4 extension
+        //zk: every leaf ends with its salt
+        let base_leaf_size: u64 = (base_element_size * elements_per_leaf + fri_config.salt_bytes())
+            .try_into()
+            .unwrap();
+        let ext_leaf_size: u64 = (ext_element_size * elements_per_leaf + fri_config.salt_bytes())
+            .try_into()
+            .unwrap();
+        let nr: usize = num_rounds.try_into().unwrap();
+        let mut layer_size = size >> (first_round * fri_config.log_folding_factor() as usize);
+        for r in first_round..nr {
+            let num_leaves: usize = layer_size / elements_per_leaf;
+            debug!("round {:?}, leafs verifier {:?}", r, num_leaves);
+            let leaf_size = if r == 0 { base_leaf_size } else { ext_leaf_size };
+            //every path of the layer is checked against the one commitment of the layer
+            friproof.layer_openings[r - first_round]
+                .authenticate(
+                    &friproof.layer_commitments[r - first_round],
+                    &fri_config.commit_config,
+                    num_leaves,
+                    leaf_size,
+                )
+                .map_err(|leaf| FriError::MerklePath { layer: r, leaf })?;
+            //this should work for any folding factor
+            layer_size /= fri_config.folding_factor;
         }
-        rq += nr;
-    }
-    //top layer index:
-    let mut rq: usize = 0;
-    for _ in 0..fri_config.num_queries {
-        top_indices.push(indices[rq]);
-        rq += nr;
-    }
-    //index_check sanity
-    for (i, j) in query_indices.iter().zip(top_indices) {
-        assert_eq!(*i, j);
-    }
 
-    //collinearity check
-    let mut rq: usize = 0;
-    let two_inv: F = F::from(2u32).inv();
-    let nr: usize = num_rounds.try_into().unwrap();
-    debug!("leafs {:?}", leafs);
-    debug!("leafs sym {:?}", leafs_sym);
-    for q in 0..fri_config.num_queries {
-        let leafs_q = leafs[rq..rq + nr].to_vec();
-        let leafs_sym_q = leafs_sym[rq..rq + nr].to_vec();
-        let index_q = indices[rq..rq + nr].to_vec();
-        let index_symq = indices_sym[rq..rq + nr].to_vec();
+        //read layer commitments from proof and gen challenge, one root per layer
+        //a first layer committed outside the proof is already in the transcript
+        let mut challenge_vec: Vec<E> = Vec::<E>::new();
+        let mut deep_points: Vec<E> = Vec::<E>::new();
         for r in 0..nr {
-            let rou:F = get_root_of_unity::<F>((size / (1 << r)).try_into().unwrap()).unwrap();
-            let rou_inv = rou.inv();
-            let l_even = (leafs_q[r] + leafs_sym_q[r]) * two_inv;
-            let l_odd = (leafs_q[r] - leafs_sym_q[r])
-                * two_inv
-                * (pow(rou_inv, index_q[r].try_into().unwrap()));
-            let l_next = l_even + challenge_vec[r] * l_odd;
-            debug!("queryno:{:?}, round: {:?},Index: {:?}, leaf: {:?}, Index_sym: {:?}  ,leaf_sym: {:?}, computed_next_leaf: {:?}",q,r,index_q[r],leafs_q[r],index_symq[r], leafs_sym_q[r],l_next);
-            if r == nr - 1 {
-                assert_eq!(
-                    l_next,
-                    friproof.final_poly[index_q[r] % fri_config.stopping_size],
-                    "collinearity test failed at final poly"
+            if r >= first_round {
+                <Transcript as TranscriptProtocol<F>>::append_commitment(
+                    transcript,
+                    b"commit",
+                    &friproof.layer_commitments[r - first_round],
                 );
-            } else {
-                assert_eq!(
-                    l_next,
-                    leafs_q[r + 1],
-                    "Collinearity test failed at round {:?}",
-                    r
+            }
+            challenge_vec.push(<Transcript as TranscriptProtocol<E>>::challenge_scalar(
+                transcript,
+                b"challenge",
+            ));
+            if fri_config.fri_mode == FriMode::Deep {
+                deep_points.push(<Transcript as TranscriptProtocol<E>>::challenge_scalar(
+                    transcript,
+                    b"deep_point",
+                ));
+                <Transcript as TranscriptProtocol<E>>::append_root(
+                    transcript,
+                    b"deep_value",
+                    &friproof.deep_values[r],
                 );
             }
         }
-        rq += nr;
-    }
 
-    Ok(true)
+        debug!("challenge_vec {:?}", challenge_vec);
+
+        //nonce check
+        let current_challenge: F =
+            <Transcript as TranscriptProtocol<F>>::challenge_scalar(transcript, b"challenge");
+        debug!("POW_challenge {:?}", current_challenge);
+        let nonce: u64 = friproof.pow_nonce;
+        debug!("nonce {:?}", nonce);
+        if !verify_pow(
+            fri_config.pow_hash,
+            fri_config.pow_bits,
+            &current_challenge.to_bytes_le(),
+            nonce,
+        ) {
+            return Err(FriError::ProofOfWork);
+        }
+
+        //add nonce to transcript
+        <Transcript as TranscriptProtocol<F>>::add_nonce(transcript, nonce);
+
+        let (query_indices, duplicates) = sample_distinct_queries::<F>(
+            transcript,
+            fri_config.num_queries,
+            size / fri_config.folding_factor,
+        );
+        debug!("verifier: {:?} duplicate query draws dropped", duplicates);
+        debug!("top layer query_indices {:?}", query_indices);
+        let first_layer_values: Vec<Vec<E>> = match first_layer {
+            Some(first_layer) => first_layer(&query_indices)?,
+            None => Vec::new(),
+        };
+        if first_round == 1
+            && (first_layer_values.len() != query_indices.len()
+                || first_layer_values.iter().any(|values| values.len() != fri_config.folding_factor))
+        {
+            return Err(FriError::MalformedProof("first layer values do not match the queries"));
+        }
+        let roll_in_values: Vec<Vec<E>> = match roll_ins {
+            Some(roll_ins) => roll_ins(&query_indices)?,
+            None => Vec::new(),
+        };
+        if roll_ins.is_some()
+            && (roll_in_values.len() != query_indices.len()
+                || roll_in_values.iter().any(|values| values.len() != nr))
+        {
+            return Err(FriError::MalformedProof("roll in values do not match the queries"));
+        }
+
+        //for each query and round we collect the whole coset of folding_factor leafs and their indices
+        //coset of index i in a layer of size n is i + j*n/k for j in 0..k
+        let mut coset_leafs: Vec<Vec<E>> = Vec::<Vec<E>>::new();
+        let mut coset_indices: Vec<Vec<usize>> = Vec::<Vec<usize>>::new();
+
+        //positions are derived from the sampled top index, the opened leaf indices only have to match
+        let nr: usize = num_rounds.try_into().unwrap();
+        let k = fri_config.folding_factor;
+        for (q, &top_index) in query_indices.iter().enumerate() {
+            let mut layer_size = size;
+            for r in 0..nr {
+                let element_size = if r == 0 { base_element_size } else { ext_element_size };
+                let coset_stride = layer_size / k;
+                //the query folds through index top_index mod n_r/k of every layer
+                let index = top_index % coset_stride;
+                coset_indices.push((0..k).map(|j| index + j * coset_stride).collect());
+                layer_size /= k;
+                if r < first_round {
+                    coset_leafs.push(first_layer_values[q].clone());
+                    continue;
+                }
+                let opening = &friproof.layer_openings[r - first_round];
+                let mut leafs_r: Vec<E> = Vec::<E>::with_capacity(k);
+                for (slot, i) in (q * leafs_per_query..(q + 1) * leafs_per_query).enumerate() {
+                    //Single: leaf slot is sibling index + slot*n/k, Coset: the one leaf is index
+                    let expected = index + slot * coset_stride;
+                    let found = opening.leaf_indices[i] as usize;
+                    if found != expected {
+                        return Err(FriError::QueryIndexMismatch {
+                            query: q,
+                            round: r,
+                            expected,
+                            found,
+                        });
+                    }
+                    //leaf sizes were checked by the merkle verification, the salt is not read
+                    let values_size = element_size * elements_per_leaf;
+                    let values = &opening.leaves[i][..values_size];
+                    for element in values.chunks(element_size) {
+                        //first layer leaves are base field elements, lift them to compare in E
+                        let leaf = if r == 0 {
+                            lift::<F, E>(&F::from_bytes_le(element))
+                        } else {
+                            E::from_bytes_le(element)
+                        };
+                        leafs_r.push(leaf);
+                    }
+                }
+                coset_leafs.push(leafs_r);
+            }
+        }
+
+        //folding check: interpolate each coset and evaluate at the challenge, in the extension field
+        //for folding factor 2 this is the usual collinearity check
+        let mut rq: usize = 0;
+        let domain_offset: F = F::from_u32(fri_config.domain_offset);
+        let (Some(&top_rou), Some(&zeta)) = (
+            self.rous.get(size.ilog2() as usize),
+            self.rous.get(fri_config.log_folding_factor() as usize),
+        ) else {
+            return Err(FriError::MalformedProof("no root of unity for domain size"));
+        };
+        //every coset is x*zeta^j with zeta = w_r^{n_r/k}, the same k-th root of unity in every layer
+        let coset_roots: Vec<F> = iter::successors(Some(F::one()), |&root| Some(root * zeta))
+            .take(k)
+            .collect();
+        for q in 0..fri_config.num_queries {
+            let mut layer_size = size;
+            //x = g_r*w_r^i, first point of the query's coset in layer r, only the top one needs a pow
+            //opened indices are at most the layer size, which fits in u32
+            let mut x: F = domain_offset * pow(top_rou, coset_indices[rq][0] as u32);
+            for r in 0..nr {
+                let leafs_qr = &coset_leafs[rq + r];
+                let index_qr = &coset_indices[rq + r];
+                let coset_points: Vec<E> = coset_roots
+                    .iter()
+                    .map(|&root| lift::<F, E>(&(x * root)))
+                    .collect();
+                let mut l_next = interpolate_and_evaluate::<E>(&coset_points, leafs_qr, challenge_vec[r]);
+                debug!("queryno:{:?}, round: {:?}, Indices: {:?}, leafs: {:?}, computed_next_leaf: {:?}",q,r,index_qr,leafs_qr,l_next);
+                //the folded value sits at index i of the next layer, which is sibling i/(n_{r+1}/k) of its coset
+                let next_index = index_qr[0];
+                layer_size /= k;
+                //x^k = g_{r+1}*w_{r+1}^i, the point of the folded value, on the final domain after the last round
+                let next_x: F = pow(x, k as u32);
+                let next_point = lift::<F, E>(&next_x);
+                if fri_config.fri_mode == FriMode::Deep {
+                    //the next layer is the DEEP quotient (g(X) - g(z))/(X - z) of the fold g
+                    if next_point == deep_points[r] {
+                        return Err(FriError::MalformedProof("deep point in the evaluation domain"));
+                    }
+                    l_next = (l_next - friproof.deep_values[r]) * (next_point - deep_points[r]).inv();
+                }
+                if r == nr - 1 {
+                    if l_next != evaluate_poly(&friproof.final_poly, next_point) {
+                        return Err(FriError::FinalPoly { query: q });
+                    }
+                } else {
+                    let sibling = (next_index / (layer_size / k)) % k;
+                    //columns of the next layer's height were added to it before the commitment
+                    if let Some(values) = roll_in_values.get(q) {
+                        l_next = l_next + values[r + 1];
+                    }
+                    if l_next != coset_leafs[rq + r + 1][sibling] {
+                        return Err(FriError::Collinearity { query: q, round: r });
+                    }
+                    //the next coset starts at next_x*zeta^{-sibling}
+                    x = next_x * coset_roots[(k - sibling) % k];
+                }
+            }
+            rq += nr;
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,62 @@
+// Fixtures shared by the integration tests, `mod common;` in each test file.
+// Every test binary uses a part of them.
+#![allow(dead_code)]
+
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use merlin::Transcript;
+
+use fri_poc::data_structures::*;
+use fri_poc::error::FriError;
+use fri_poc::prover::prove;
+use fri_poc::verifier::verify;
+
+pub const DOMAIN_SEPARATOR: &[u8] = b"my_Shitty_FRI";
+pub const PUBLIC_INPUT: &[u8] = b"public";
+
+// plain FRI without zk, blow up 4 and 50 queries down to 16 elements,
+// tests change the rest with `Friconfig { zk: true, ..fri_config(..) }`
+pub fn fri_config(folding_factor: usize, leaf_layout: LeafLayout, domain_offset: u32) -> Friconfig {
+    Friconfig {
+        blow_up_factor: 4,
+        folding_factor,
+        pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
+        num_queries: 50,
+        stopping_size: 16,
+        leaf_layout,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset,
+        commit_config: CommitConfig::default(),
+    }
+}
+
+// prove with a fresh transcript labelled `label`
+pub fn prove_fri(label: &'static [u8], fri_config: Friconfig, code_word: Vec<Fr>) -> Friproof<Ext> {
+    let mut prover_transcript = Transcript::new(label);
+    prove::<Fr, Ext>(
+        fri_config,
+        DOMAIN_SEPARATOR,
+        PUBLIC_INPUT,
+        &mut prover_transcript,
+        code_word,
+    )
+}
+
+// verify with a fresh transcript labelled `label`
+pub fn verify_fri(
+    label: &'static [u8],
+    fri_config: Friconfig,
+    log_domain_size: u32,
+    friproof: Friproof<Ext>,
+) -> Result<(), FriError> {
+    let mut verifier_transcript = Transcript::new(label);
+    verify::<Fr, Ext>(
+        fri_config,
+        log_domain_size,
+        DOMAIN_SEPARATOR,
+        PUBLIC_INPUT,
+        friproof,
+        &mut verifier_transcript,
+    )
+}
@@ -1,11 +1,18 @@
-use icicle_babybear::field::ScalarField as Fr;
-use icicle_core::traits::{Arithmetic, GenerateRandom, Invertible};
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use icicle_core::{
+    bignum::BigNum,
+    traits::{Arithmetic, GenerateRandom, Invertible},
+};
 use log::{debug, info};
 use merlin::Transcript;
 
+use fri_poc::error::FriError;
 use fri_poc::prover::*;
 use fri_poc::utils::*;
-use fri_poc::{data_structures::*, verifier::verify};
+use fri_poc::{
+    data_structures::*,
+    verifier::{verify, FriVerifier},
+};
 use std::time::Instant;
 
 #[test]
@@ -16,8 +23,14 @@ fn diagnostic_prover_test() {
         blow_up_factor: 4,
         folding_factor: 2,
         pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
         num_queries: 1,
-        stopping_size: 1, //2^0
+        stopping_size: 16, //at least the blow up factor
+        leaf_layout: LeafLayout::Single,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset: 1,
+        commit_config: CommitConfig::default(),
     };
 
     let input_data: Vec<Fr> = vec![
@@ -44,30 +57,55 @@ fn diagnostic_prover_test() {
         //eval = 2^k and we need size = 2^k*blowup
         eval_to_eval_blowup::<Fr>(input_data.clone(), size)
     };
-    let num_rounds = size.ilog2() - fri_config.stopping_size.ilog2();
     let mut prover_transcript = Transcript::new(b"Shitty_FRI");
 
-    let friproof: Friproof<Fr> = prove::<Fr>(fri_config, &mut prover_transcript, code_word.clone());
+    let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+        fri_config,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        code_word.clone(),
+    );
     debug!("final_poly {:?}", friproof.final_poly);
 
-    let nr: usize = num_rounds.try_into().unwrap();
+    let k = fri_config.folding_factor;
     for q in 0..fri_config.num_queries {
-        let mut it = 0;
-        for query_proof in friproof.query_proofs.iter().skip(q * nr).take(nr) {
-            it += 1;
-            debug!(
-                "query number {:?}, layer number {:?}, leaf_layer {:?}, leaf_ayersym {:?}",
-                q,
-                it,
-                query_proof[0].get_leaf::<Fr>(),
-                query_proof[1].get_leaf::<Fr>()
-            );
+        for (it, opening) in friproof.layer_openings.iter().enumerate() {
+            //first layer is committed in the base field, the rest in the extension
+            let index = opening.leaf_indices[q * k];
+            let index_sym = opening.leaf_indices[q * k + 1];
+            if it == 0 {
+                debug!(
+                    "query number {:?}, layer number {:?}, leaf_layer {:?}, leaf_ayersym {:?}",
+                    q,
+                    it,
+                    (Fr::from_bytes_le(&opening.leaves[q * k]), index),
+                    (Fr::from_bytes_le(&opening.leaves[q * k + 1]), index_sym)
+                );
+            } else {
+                debug!(
+                    "query number {:?}, layer number {:?}, leaf_layer {:?}, leaf_ayersym {:?}",
+                    q,
+                    it,
+                    (Ext::from_bytes_le(&opening.leaves[q * k]), index),
+                    (Ext::from_bytes_le(&opening.leaves[q * k + 1]), index_sym)
+                );
+            }
         }
     }
+    debug!("proof size {:?} bytes", friproof.num_bytes());
 
     let mut verifier_transcript = Transcript::new(b"Shitty_FRI");
 
-    verify(fri_config, friproof, &mut verifier_transcript).unwrap();
+    verify::<Fr, Ext>(
+        fri_config,
+        size.ilog2(),
+        b"my_Shitty_FRI",
+        b"public",
+        friproof,
+        &mut verifier_transcript,
+    )
+    .unwrap();
 }
 
 // RUST_LOG=info cargo test --package fri_poc --test e2etests -- e2e_fri_test --exact --show-output
@@ -79,8 +117,14 @@ fn e2e_fri_test() {
         blow_up_factor: 4,
         folding_factor: 2,
         pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
         num_queries: 50,
         stopping_size: 256, //2^0
+        leaf_layout: LeafLayout::Single,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset: 1,
+        commit_config: CommitConfig::default(),
     };
 
     let starting_size: usize = 1 << 16;
@@ -99,9 +143,347 @@ fn e2e_fri_test() {
 
     let mut prover_transcript = Transcript::new(b"Real_FRI");
     let provertime = Instant::now();
-    let friproof: Friproof<Fr> = prove::<Fr>(fri_config, &mut prover_transcript, code_word.clone());
+    let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+        fri_config,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        code_word.clone(),
+    );
 
     let verifiertime = Instant::now();
     let mut verifier_transcript = Transcript::new(b"Real_FRI");
-    verify(fri_config, friproof, &mut verifier_transcript).unwrap();
+    verify::<Fr, Ext>(
+        fri_config,
+        size.ilog2(),
+        b"my_Shitty_FRI",
+        b"public",
+        friproof,
+        &mut verifier_transcript,
+    )
+    .unwrap();
+}
+
+// cargo test --package fri_poc --test e2etests -- e2e_fri_folding_factor_test --exact --show-output
+#[test]
+fn e2e_fri_folding_factor_test() {
+    let _ = env_logger::try_init();
+    //log(size/stopping_size) should be a multiple of log(folding_factor)
+    for (folding_factor, stopping_size) in [(4usize, 16usize), (8, 8), (16, 16)] {
+        let fri_config: Friconfig = Friconfig {
+            blow_up_factor: 4,
+            folding_factor,
+            pow_bits: 10,
+            pow_hash: HashKind::Blake2s,
+            num_queries: 50,
+            stopping_size,
+            leaf_layout: LeafLayout::Single,
+            fri_mode: FriMode::Plain,
+            zk: false,
+            domain_offset: 1,
+            commit_config: CommitConfig::default(),
+        };
+        let starting_size: usize = 1 << 10;
+        let input_data: Vec<Fr> = generate_random_vector::<Fr>(starting_size);
+        let size: usize = input_data.len() * fri_config.blow_up_factor;
+        let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data, size);
+
+        let mut prover_transcript = Transcript::new(b"Real_FRI");
+        let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+            fri_config,
+            b"my_Shitty_FRI",
+            b"public",
+            &mut prover_transcript,
+            code_word,
+        );
+        //one batch opening per layer, with every coset element of every query
+        assert_eq!(
+            friproof.layer_openings.len(),
+            fri_config.num_rounds(size) as usize
+        );
+        assert!(friproof
+            .layer_openings
+            .iter()
+            .all(|opening| opening.leaves.len() == fri_config.num_queries * folding_factor));
+
+        let mut verifier_transcript = Transcript::new(b"Real_FRI");
+        verify::<Fr, Ext>(
+            fri_config,
+            size.ilog2(),
+            b"my_Shitty_FRI",
+            b"public",
+            friproof,
+            &mut verifier_transcript,
+        )
+        .unwrap();
+    }
+}
+
+// cargo test --package fri_poc --test e2etests -- e2e_fri_coset_leaf_test --exact --show-output
+#[test]
+fn e2e_fri_coset_leaf_test() {
+    let _ = env_logger::try_init();
+    for folding_factor in [2usize, 4, 16] {
+        let mut proof_sizes: Vec<usize> = Vec::new();
+        let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+        for leaf_layout in [LeafLayout::Single, LeafLayout::Coset] {
+            let fri_config: Friconfig = Friconfig {
+                blow_up_factor: 4,
+                folding_factor,
+                pow_bits: 10,
+                pow_hash: HashKind::Blake2s,
+                num_queries: 50,
+                stopping_size: 16,
+                leaf_layout,
+                fri_mode: FriMode::Plain,
+                zk: false,
+                domain_offset: 1,
+                commit_config: CommitConfig::default(),
+            };
+            let size: usize = input_data.len() * fri_config.blow_up_factor;
+            let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data.clone(), size);
+
+            let mut prover_transcript = Transcript::new(b"Real_FRI");
+            let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+                fri_config,
+                b"my_Shitty_FRI",
+                b"public",
+                &mut prover_transcript,
+                code_word,
+            );
+            proof_sizes.push(friproof.num_bytes());
+
+            let mut verifier_transcript = Transcript::new(b"Real_FRI");
+            verify::<Fr, Ext>(
+                fri_config,
+                size.ilog2(),
+                b"my_Shitty_FRI",
+                b"public",
+                friproof,
+                &mut verifier_transcript,
+            )
+            .unwrap();
+        }
+        //one path per fold step instead of one per coset element
+        info!(
+            "folding factor {:?}: single leaf proof {:?} bytes, coset leaf proof {:?} bytes",
+            folding_factor, proof_sizes[0], proof_sizes[1]
+        );
+        assert!(proof_sizes[1] < proof_sizes[0]);
+    }
+}
+
+// cargo test --package fri_poc --test e2etests -- e2e_fri_coset_domain_test --exact --show-output
+#[test]
+fn e2e_fri_coset_domain_test() {
+    let _ = env_logger::try_init();
+    //31 generates the babybear multiplicative group, so g*H is disjoint from H
+    for (folding_factor, leaf_layout) in [(2usize, LeafLayout::Single), (4, LeafLayout::Coset)] {
+        let fri_config: Friconfig = Friconfig {
+            blow_up_factor: 4,
+            folding_factor,
+            pow_bits: 10,
+            pow_hash: HashKind::Blake2s,
+            num_queries: 50,
+            stopping_size: 16,
+            leaf_layout,
+            fri_mode: FriMode::Plain,
+            zk: false,
+            domain_offset: 31,
+            commit_config: CommitConfig::default(),
+        };
+        let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+        let size: usize = input_data.len() * fri_config.blow_up_factor;
+        let code_word: Vec<Fr> =
+            coeff_to_eval_blowup_coset::<Fr>(input_data, size, Fr::from_u32(31));
+
+        let mut prover_transcript = Transcript::new(b"Real_FRI");
+        let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+            fri_config,
+            b"my_Shitty_FRI",
+            b"public",
+            &mut prover_transcript,
+            code_word,
+        );
+        let mut verifier_transcript = Transcript::new(b"Real_FRI");
+        verify::<Fr, Ext>(
+            fri_config,
+            size.ilog2(),
+            b"my_Shitty_FRI",
+            b"public",
+            friproof.clone(),
+            &mut verifier_transcript,
+        )
+        .unwrap();
+
+        //a verifier folding on the subgroup does not accept the coset proof
+        let subgroup_config = Friconfig {
+            domain_offset: 1,
+            ..fri_config
+        };
+        let mut verifier_transcript = Transcript::new(b"Real_FRI");
+        assert!(matches!(
+            verify::<Fr, Ext>(
+                subgroup_config,
+                size.ilog2(),
+                b"my_Shitty_FRI",
+                b"public",
+                friproof,
+                &mut verifier_transcript,
+            ),
+            Err(FriError::Collinearity { .. }) | Err(FriError::FinalPoly { .. })
+        ));
+    }
+}
+
+// cargo test --package fri_poc --test e2etests -- e2e_fri_commit_config_test --exact --show-output
+#[test]
+fn e2e_fri_commit_config_test() {
+    let _ = env_logger::try_init();
+    let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    for (leaf_hash, compression, arity, leaf_layout) in [
+        (HashKind::Blake2s, HashKind::Blake2s, 4usize, LeafLayout::Single),
+        (HashKind::Blake3, HashKind::Blake3, 8, LeafLayout::Coset),
+        (HashKind::Keccak256, HashKind::Keccak256, 2, LeafLayout::Single),
+        //coset leafs of 4 base or 16 extension limbs are supported poseidon2 widths
+        (HashKind::Poseidon2, HashKind::Poseidon2, 4, LeafLayout::Coset),
+    ] {
+        let commit_config = CommitConfig {
+            leaf_hash,
+            compression,
+            arity,
+        };
+        let fri_config: Friconfig = Friconfig {
+            blow_up_factor: 4,
+            folding_factor: 4,
+            pow_bits: 10,
+            pow_hash: HashKind::Blake2s,
+            num_queries: 50,
+            stopping_size: 16,
+            leaf_layout,
+            fri_mode: FriMode::Plain,
+            zk: false,
+            domain_offset: 1,
+            commit_config,
+        };
+        let size: usize = input_data.len() * fri_config.blow_up_factor;
+        let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data.clone(), size);
+
+        let mut prover_transcript = Transcript::new(b"Real_FRI");
+        let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+            fri_config,
+            b"my_Shitty_FRI",
+            b"public",
+            &mut prover_transcript,
+            code_word,
+        );
+        info!("{:?}: proof {:?} bytes", commit_config, friproof.num_bytes());
+        let mut verifier_transcript = Transcript::new(b"Real_FRI");
+        verify::<Fr, Ext>(
+            fri_config,
+            size.ilog2(),
+            b"my_Shitty_FRI",
+            b"public",
+            friproof.clone(),
+            &mut verifier_transcript,
+        )
+        .unwrap();
+
+        //the verifier has to rebuild the same tree
+        let other_arity = Friconfig {
+            commit_config: CommitConfig {
+                arity: if arity == 2 { 4 } else { 2 },
+                ..commit_config
+            },
+            ..fri_config
+        };
+        let leaf = friproof.layer_openings[0].leaf_indices[0];
+        let mut verifier_transcript = Transcript::new(b"Real_FRI");
+        assert_eq!(
+            verify::<Fr, Ext>(
+                other_arity,
+                size.ilog2(),
+                b"my_Shitty_FRI",
+                b"public",
+                friproof,
+                &mut verifier_transcript,
+            ),
+            Err(FriError::MerklePath { layer: 0, leaf })
+        );
+    }
+}
+
+// cargo test --package fri_poc --test e2etests -- e2e_reusable_prover_verifier_test --exact --show-output
+#[test]
+fn e2e_reusable_prover_verifier_test() {
+    let _ = env_logger::try_init();
+    let fri_config: Friconfig = Friconfig {
+        blow_up_factor: 4,
+        folding_factor: 4,
+        pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
+        num_queries: 50,
+        stopping_size: 16,
+        leaf_layout: LeafLayout::Coset,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset: 31,
+        commit_config: CommitConfig::default(),
+    };
+    let max_log_size: u32 = 12;
+    //setup once, then every proof reuses the twiddles and roots of unity
+    let setup_time = Instant::now();
+    let prover = FriProver::<Fr, Ext>::new(fri_config, max_log_size);
+    let verifier = FriVerifier::<Fr>::new(fri_config, max_log_size);
+    info!("setup {:?}", setup_time.elapsed());
+    //codewords of the max size and a smaller one, on domain_offset*H
+    for num_coeffs in [1usize << 10, 1 << 10, 1 << 8] {
+        let code_word: Vec<Fr> = prover.lde(generate_random_vector::<Fr>(num_coeffs));
+        let size = code_word.len();
+        let prove_time = Instant::now();
+        let mut prover_transcript = Transcript::new(b"Real_FRI");
+        let friproof: Friproof<Ext> =
+            prover.prove(b"my_Shitty_FRI", b"public", &mut prover_transcript, code_word);
+        info!("size {:?}: prove {:?}", size, prove_time.elapsed());
+        let mut verifier_transcript = Transcript::new(b"Real_FRI");
+        assert_eq!(
+            verifier.verify(
+                size.ilog2(),
+                b"my_Shitty_FRI",
+                b"public",
+                friproof,
+                &mut verifier_transcript,
+            ),
+            Ok(())
+        );
+    }
+}
+
+// cargo test --package fri_poc --test e2etests -- codeword_below_stopping_size_is_rejected --exact --show-output
+#[test]
+#[should_panic(expected = "codeword smaller than the stopping size")]
+fn codeword_below_stopping_size_is_rejected() {
+    let fri_config: Friconfig = Friconfig {
+        blow_up_factor: 4,
+        folding_factor: 2,
+        pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
+        num_queries: 4,
+        stopping_size: 256,
+        leaf_layout: LeafLayout::Single,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset: 1,
+        commit_config: CommitConfig::default(),
+    };
+    //64 elements, the size checks come before any ilog2 difference
+    let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(generate_random_vector::<Fr>(16), 64);
+    let mut prover_transcript = Transcript::new(b"Real_FRI");
+    prove::<Fr, Ext>(
+        fri_config,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        code_word,
+    );
 }
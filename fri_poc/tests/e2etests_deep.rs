@@ -0,0 +1,82 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use icicle_core::traits::Arithmetic;
+use log::info;
+
+use fri_poc::data_structures::*;
+use fri_poc::error::FriError;
+use fri_poc::utils::*;
+
+mod common;
+use common::*;
+
+const LABEL: &[u8] = b"Deep_FRI";
+
+fn deep_config(folding_factor: usize, leaf_layout: LeafLayout, domain_offset: u32) -> Friconfig {
+    Friconfig {
+        fri_mode: FriMode::Deep,
+        ..fri_config(folding_factor, leaf_layout, domain_offset)
+    }
+}
+
+// cargo test --package fri_poc --test e2etests_deep -- e2e_deep_fri_test --exact --show-output
+#[test]
+fn e2e_deep_fri_test() {
+    let _ = env_logger::try_init();
+    let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    for folding_factor in [2usize, 4, 16] {
+        for leaf_layout in [LeafLayout::Single, LeafLayout::Coset] {
+            for domain_offset in [1u32, 31] {
+                let fri_config = deep_config(folding_factor, leaf_layout, domain_offset);
+                let size: usize = input_data.len() * fri_config.blow_up_factor;
+                let code_word: Vec<Fr> = coeff_to_eval_blowup_coset::<Fr>(
+                    input_data.clone(),
+                    size,
+                    Fr::from_u32(domain_offset),
+                );
+                let friproof = prove_fri(LABEL, fri_config, code_word);
+                assert_eq!(friproof.deep_values.len(), fri_config.num_rounds(size) as usize);
+                info!(
+                    "folding factor {:?}, {:?}, offset {:?}: deep proof {:?} bytes",
+                    folding_factor,
+                    leaf_layout,
+                    domain_offset,
+                    friproof.num_bytes()
+                );
+                assert_eq!(verify_fri(LABEL, fri_config, size.ilog2(), friproof), Ok(()));
+            }
+        }
+    }
+}
+
+// cargo test --package fri_poc --test e2etests_deep -- deep_values_are_checked --exact --show-output
+#[test]
+fn deep_values_are_checked() {
+    let fri_config = deep_config(4, LeafLayout::Coset, 1);
+    let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    let size: usize = input_data.len() * fri_config.blow_up_factor;
+    let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data, size);
+    let friproof = prove_fri(LABEL, fri_config, code_word);
+
+    //a wrong value changes every later challenge and the quotient the queries are checked against
+    let mut tampered = friproof.clone();
+    tampered.deep_values[1] = tampered.deep_values[1] + Ext::one();
+    assert!(verify_fri(LABEL, fri_config, size.ilog2(), tampered).is_err());
+
+    let mut missing = friproof.clone();
+    missing.deep_values.pop();
+    assert!(matches!(
+        verify_fri(LABEL, fri_config, size.ilog2(), missing),
+        Err(FriError::MalformedProof(_))
+    ));
+
+    //the mode is part of the config, a plain verifier does not take a deep proof
+    let plain_config = Friconfig {
+        fri_mode: FriMode::Plain,
+        zk: false,
+        ..fri_config
+    };
+    assert!(matches!(
+        verify_fri(LABEL, plain_config, size.ilog2(), friproof),
+        Err(FriError::MalformedProof(_))
+    ));
+}
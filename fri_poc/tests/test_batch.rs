@@ -0,0 +1,152 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use merlin::Transcript;
+
+use fri_poc::batch::*;
+use fri_poc::data_structures::*;
+use fri_poc::error::FriError;
+use fri_poc::utils::*;
+
+const FRI_CONFIG: Friconfig = Friconfig {
+    blow_up_factor: 4,
+    folding_factor: 4,
+    pow_bits: 10,
+    pow_hash: HashKind::Blake2s,
+    num_queries: 30,
+    stopping_size: 16,
+    leaf_layout: LeafLayout::Coset,
+    fri_mode: FriMode::Plain,
+    zk: false,
+    domain_offset: 1,
+    commit_config: CommitConfig::default(),
+};
+const LOG_DOMAIN_SIZE: u32 = 12;
+
+//columns with `num_coeffs[i]` random coefficients, all on the same domain
+fn columns(num_coeffs: &[usize]) -> Vec<Vec<Fr>> {
+    num_coeffs
+        .iter()
+        .map(|&n| coeff_to_eval_blowup::<Fr>(generate_random_vector::<Fr>(n), 1 << LOG_DOMAIN_SIZE))
+        .collect()
+}
+
+fn prove_columns(code_words: Vec<Vec<Fr>>, degree_bounds: &[usize]) -> BatchFriproof<Ext> {
+    let mut prover_transcript = Transcript::new(b"Batch_FRI");
+    prove_batch::<Fr, Ext>(
+        FRI_CONFIG,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        code_words,
+        degree_bounds,
+    )
+}
+
+fn verify_columns(proof: BatchFriproof<Ext>, degree_bounds: &[usize]) -> Result<(), FriError> {
+    let column_sizes: Vec<usize> = vec![1 << LOG_DOMAIN_SIZE; degree_bounds.len()];
+    verify_columns_of_sizes(proof, &column_sizes, degree_bounds)
+}
+
+fn verify_columns_of_sizes(
+    proof: BatchFriproof<Ext>,
+    column_sizes: &[usize],
+    degree_bounds: &[usize],
+) -> Result<(), FriError> {
+    let mut verifier_transcript = Transcript::new(b"Batch_FRI");
+    verify_batch::<Fr, Ext>(
+        FRI_CONFIG,
+        LOG_DOMAIN_SIZE,
+        column_sizes,
+        degree_bounds,
+        b"my_Shitty_FRI",
+        b"public",
+        proof,
+        &mut verifier_transcript,
+    )
+}
+
+// cargo test --package fri_poc --test test_batch -- batch_with_degree_correction_verifies --exact --show-output
+#[test]
+fn batch_with_degree_correction_verifies() {
+    let degree_bounds: Vec<usize> = vec![1 << 10, 1 << 10, 1 << 9, 100, 1];
+    let proof = prove_columns(columns(&degree_bounds), &degree_bounds);
+    println!(
+        "{:?} columns, batched proof {:?} bytes",
+        degree_bounds.len(),
+        proof.num_bytes()
+    );
+    assert_eq!(verify_columns(proof, &degree_bounds), Ok(()));
+}
+
+#[test]
+fn column_above_its_bound_is_rejected() {
+    //the second column has 2^10 coefficients but claims 2^9, still below the common bound
+    let claimed: Vec<usize> = vec![1 << 10, 1 << 9];
+    let proof = prove_columns(columns(&[1 << 10, 1 << 10]), &claimed);
+    assert!(verify_columns(proof, &claimed).is_err());
+}
+
+#[test]
+fn tampered_row_is_rejected() {
+    let degree_bounds: Vec<usize> = vec![1 << 10, 1 << 8];
+    let mut proof = prove_columns(columns(&degree_bounds), &degree_bounds);
+    proof.matrix_opening.rows[0][0][0] ^= 1;
+    assert_eq!(
+        verify_columns(proof.clone(), &degree_bounds),
+        Err(FriError::MerklePath {
+            layer: 0,
+            leaf: proof.matrix_opening.indices[0]
+        })
+    );
+
+    //rows are bound to the column count through the leaf size
+    proof.matrix_opening.rows[0][0][0] ^= 1;
+    assert!(verify_columns(proof, &[1 << 10]).is_err());
+}
+
+//columns of length 2^12, 2^10 and 2^8, each with its own number of coefficients
+fn mixed_columns(column_sizes: &[usize], num_coeffs: &[usize]) -> Vec<Vec<Fr>> {
+    column_sizes
+        .iter()
+        .zip(num_coeffs.iter())
+        .map(|(&column_size, &n)| coeff_to_eval_blowup::<Fr>(generate_random_vector::<Fr>(n), column_size))
+        .collect()
+}
+
+// cargo test --package fri_poc --test test_batch -- mixed_lengths_verify --exact --show-output
+#[test]
+fn mixed_lengths_verify() {
+    //the 2^10 columns roll into the first folded layer, the 2^8 one into the second
+    let column_sizes: Vec<usize> = vec![1 << 12, 1 << 10, 1 << 12, 1 << 8, 1 << 10];
+    let degree_bounds: Vec<usize> = vec![1 << 10, 1 << 8, 100, 1 << 6, 7];
+    let proof = prove_columns(mixed_columns(&column_sizes, &degree_bounds), &degree_bounds);
+    println!(
+        "{:?} columns of 3 lengths, batched proof {:?} bytes",
+        degree_bounds.len(),
+        proof.num_bytes()
+    );
+    assert_eq!(verify_columns_of_sizes(proof.clone(), &column_sizes, &degree_bounds), Ok(()));
+
+    //the verifier has to know the lengths
+    let mut wrong_sizes = column_sizes.clone();
+    wrong_sizes.swap(3, 4);
+    assert!(verify_columns_of_sizes(proof, &wrong_sizes, &degree_bounds).is_err());
+}
+
+#[test]
+fn short_column_above_its_bound_is_rejected() {
+    //the 2^10 column has 2^8 coefficients but claims 2^7
+    let column_sizes: Vec<usize> = vec![1 << 12, 1 << 10];
+    let claimed: Vec<usize> = vec![1 << 10, 1 << 7];
+    let proof = prove_columns(mixed_columns(&column_sizes, &[1 << 10, 1 << 8]), &claimed);
+    assert!(verify_columns_of_sizes(proof, &column_sizes, &claimed).is_err());
+
+    //a short row that does not match the root
+    let degree_bounds: Vec<usize> = vec![1 << 10, 1 << 8];
+    let mut proof = prove_columns(mixed_columns(&column_sizes, &degree_bounds), &degree_bounds);
+    proof.matrix_opening.rows[0][1][0] ^= 1;
+    //reported at the tampered leaf, or at a later leaf opening the same short row
+    assert!(matches!(
+        verify_columns_of_sizes(proof, &column_sizes, &degree_bounds),
+        Err(FriError::MerklePath { layer: 0, .. })
+    ));
+}
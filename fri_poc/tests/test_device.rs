@@ -0,0 +1,79 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use merlin::Transcript;
+
+use fri_poc::data_structures::*;
+use fri_poc::device::DeviceFriProver;
+use fri_poc::prover::FriProver;
+use fri_poc::utils::*;
+use fri_poc::verifier::verify;
+
+mod common;
+use common::*;
+
+// cargo test --package fri_poc --test test_device -- device_prover_matches_host_prover --exact --show-output
+#[test]
+fn device_prover_matches_host_prover() {
+    //the device buffers live in host memory on the cpu backend
+    set_backend_cpu();
+    //2^16 is above the size the old gpu example segfaulted at, first so the ntt domain is
+    //initialized once at its largest size
+    for (folding_factor, leaf_layout, domain_offset, log_size) in [
+        (2usize, LeafLayout::Coset, 31u32, 16u32),
+        (2, LeafLayout::Single, 1, 12),
+        (4, LeafLayout::Coset, 31, 12),
+        (16, LeafLayout::Coset, 1, 12),
+    ] {
+        let fri_config = fri_config(folding_factor, leaf_layout, domain_offset);
+        let coeffs: Vec<Fr> = generate_random_vector::<Fr>(1 << (log_size - 2));
+        let host_prover = FriProver::<Fr, Ext>::new(fri_config, log_size);
+        let device_prover = DeviceFriProver::<Fr, Ext>::new(fri_config, log_size);
+
+        let mut host_transcript = Transcript::new(b"Device_FRI");
+        let host_proof = host_prover.prove(
+            DOMAIN_SEPARATOR,
+            PUBLIC_INPUT,
+            &mut host_transcript,
+            host_prover.lde(coeffs.clone()),
+        );
+        let mut device_transcript = Transcript::new(b"Device_FRI");
+        let device_proof =
+            device_prover.prove(DOMAIN_SEPARATOR, PUBLIC_INPUT, &mut device_transcript, coeffs);
+
+        //same layers, so the same roots, challenges, queries and openings
+        assert_eq!(device_proof.layer_commitments, host_proof.layer_commitments);
+        assert_eq!(device_proof.layer_openings, host_proof.layer_openings);
+        assert_eq!(device_proof.final_poly, host_proof.final_poly);
+        assert_eq!(device_proof.pow_nonce, host_proof.pow_nonce);
+
+        let mut verifier_transcript = Transcript::new(b"Device_FRI");
+        assert_eq!(
+            verify::<Fr, Ext>(
+                fri_config,
+                log_size,
+                DOMAIN_SEPARATOR,
+                PUBLIC_INPUT,
+                device_proof,
+                &mut verifier_transcript,
+            ),
+            Ok(())
+        );
+    }
+}
+
+// cargo test --package fri_poc --test test_device -- device_prover_checks_the_config --exact --show-output
+#[test]
+#[should_panic(expected = "blow up factor should be a power of two at most the stopping size")]
+fn device_prover_checks_the_config() {
+    //same entry checks as FriProver
+    set_backend_cpu();
+    let mut fri_config = fri_config(2, LeafLayout::Single, 1);
+    fri_config.stopping_size = 2;
+    let device_prover = DeviceFriProver::<Fr, Ext>::new(fri_config, 12);
+    let mut transcript = Transcript::new(b"Device_FRI");
+    device_prover.prove(
+        DOMAIN_SEPARATOR,
+        PUBLIC_INPUT,
+        &mut transcript,
+        generate_random_vector::<Fr>(1 << 10),
+    );
+}
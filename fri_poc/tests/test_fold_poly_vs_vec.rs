@@ -1,12 +1,15 @@
 use std::iter;
 
 use icicle_core::{
-    bignum::BigNum, merkle::MerkleTree, ntt::{get_root_of_unity, ntt, NTTConfig, NTTDir}, polynomials::UnivariatePolynomial, traits::{Arithmetic, GenerateRandom, Invertible}
+    bignum::BigNum, merkle::{MerkleProof, MerkleTree}, ntt::{get_root_of_unity, ntt, NTTConfig, NTTDir}, polynomials::UnivariatePolynomial, traits::{Arithmetic, GenerateRandom, Invertible}
 };
 
 use icicle_runtime::memory::HostSlice;
 
-use icicle_babybear::{field::ScalarField as Fr, polynomials::DensePolynomial};
+use icicle_babybear::{
+    field::{ExtensionField as Ext, ScalarField as Fr},
+    polynomials::DensePolynomial,
+};
 
 use fri_poc::data_structures::*;
 use fri_poc::utils::*;
@@ -101,6 +104,36 @@ pub fn poly_fold_vector_fold_sanity_no_coset() {
     println!("manual compute {:?}", f_f);
 }
 
+// reference k-ary fold, a fresh inverse domain per binary fold: log2(k) binary folds with
+// challenges alpha, alpha^2, alpha^4,... inv_domain is the full (1,w^{-1},..) of size n/2 for the
+// domain of size n the code word was first evaluated on, on the coset g*H' with shift_inv = g^{-1}
+fn fold_by_factor(
+    layer: &mut Current_layer<Fr>,
+    inv_domain: &[Fr],
+    two_inv: &Fr,
+    alpha: Fr,
+    shift_inv: Fr,
+    folding_factor: usize,
+) -> Vec<Fr> {
+    let domain_size = 2 * inv_domain.len();
+    let mut alpha_pow = alpha;
+    let mut shift_inv_pow = shift_inv;
+    for _ in 0..folding_factor.ilog2() {
+        //domain of the current layer is every (n/len)-th element, times g^{-2^s}
+        let stride = domain_size / layer.current_code_word.len();
+        let mut inv_domain_layer: Vec<Fr> = inv_domain
+            .iter()
+            .step_by(stride)
+            .map(|&x| x * shift_inv_pow)
+            .collect();
+        layer.current_code_word =
+            layer.fold_evals_precompute_domain(&mut inv_domain_layer, two_inv, alpha_pow);
+        alpha_pow = alpha_pow * alpha_pow;
+        shift_inv_pow = shift_inv_pow * shift_inv_pow;
+    }
+    layer.current_code_word.clone()
+}
+
 // original domain H: [1,w,w^2,...]
 // folded original domain H^2: [1,w^2,w^4,...]
 // coset:D: g[1,w,w^2,...]
@@ -168,7 +201,96 @@ pub fn poly_fold_vector_fold_sanity_coset() {
         "p fold evals : coset gen D^2 {:?}",
         coset_sq_eval_slice.as_slice().to_vec()
     );
+    assert_eq!(v_fold, coset_sq_evals);
+
+    //the coset LDE helper gives the same evals as the raw ntt
+    assert_eq!(
+        coeff_to_eval_blowup_coset::<Fr>(v.clone(), size, coset_gen3),
+        v_eval_coset_vec
+    );
+
+    //precomputed subgroup domain with the shift applied in the fold, as in the prover
+    let rou_inv = rou_baby_bear.inv();
+    let inv_domain: Vec<Fr> = iter::successors(Some(Fr::one()), |p| Some(*p * rou_inv))
+        .take(size / 2)
+        .collect();
+    let mut frilayer = Current_layer::<Fr> {
+        current_code_word: v_eval_coset_vec.clone(),
+    };
+    let v_fold_precompute = fold_by_factor(
+        &mut frilayer,
+        &inv_domain,
+        &Fr::from_u32(2).inv(),
+        gamma,
+        coset_gen3.inv(),
+        2,
+    );
+    assert_eq!(v_fold_precompute, coset_sq_evals);
 }
+
+// folding by 4 on the coset gH lands on g^4 H^4
+#[test]
+pub fn fold_by_factor_coset_matches_poly() {
+    let size: usize = 16;
+    let coeffs: Vec<Fr> = (1..=size as u32).map(Fr::from_u32).collect();
+    let coset_gen: Fr = Fr::from_u32(31);
+    let evals = coeff_to_eval_blowup_coset::<Fr>(coeffs.clone(), size, coset_gen);
+
+    let rou: Fr = get_root_of_unity::<Fr>(size.try_into().unwrap()).unwrap();
+    let rou_inv = rou.inv();
+    let inv_domain: Vec<Fr> = iter::successors(Some(Fr::one()), |p| Some(*p * rou_inv))
+        .take(size / 2)
+        .collect();
+    let gamma = Fr::from_u32(7);
+    let mut frilayer = Current_layer::<Fr> {
+        current_code_word: evals,
+    };
+    let v_fold = fold_by_factor(&mut frilayer, &inv_domain, &Fr::from_u32(2).inv(), gamma, coset_gen.inv(), 4);
+
+    //p_fold = sum_j gamma^j p_j(X) where p(X) = sum_j X^j p_j(X^4)
+    let poly = DensePolynomial::from_coeffs(HostSlice::from_slice(&coeffs), size);
+    let p_fold = fold_poly(fold_poly(poly, gamma), gamma * gamma);
+    let coset_gen4 = pow(coset_gen, 4);
+    let rou4 = pow(rou, 4);
+    let coset_domain4: Vec<Fr> = iter::successors(Some(coset_gen4), |p| Some(*p * rou4))
+        .take(size / 4)
+        .collect();
+    let mut expected = vec![Fr::zero(); size / 4];
+    p_fold.eval_on_domain(
+        HostSlice::from_slice(&coset_domain4),
+        HostSlice::from_mut_slice(&mut expected[..]),
+    );
+    assert_eq!(v_fold, expected);
+}
+// the cached twiddles with the shift in the challenge fold like the per round domain
+#[test]
+pub fn fold_with_twiddles_matches_fold_by_factor() {
+    let log_size: u32 = 10;
+    let size: usize = 1 << log_size;
+    let coset_gen: Fr = Fr::from_u32(31);
+    let evals = coeff_to_eval_blowup_coset::<Fr>(generate_random_vector::<Fr>(size / 4), size, coset_gen);
+    let rou_inv = get_root_of_unity::<Fr>(size.try_into().unwrap()).unwrap().inv();
+    let inv_domain: Vec<Fr> = iter::successors(Some(Fr::one()), |p| Some(*p * rou_inv))
+        .take(size / 2)
+        .collect();
+    //the twiddles of a larger domain work for every smaller code word
+    let inv_twiddles: Vec<Vec<Fr>> = inverse_twiddles::<Fr>(log_size + 2);
+    assert_eq!(inv_twiddles[log_size as usize], inv_domain);
+    let two_inv = Fr::from_u32(2).inv();
+    let gamma = Fr::from_u32(7);
+    for folding_factor in [2usize, 4, 16] {
+        let mut frilayer = Current_layer::<Fr> {
+            current_code_word: evals.clone(),
+        };
+        let expected = fold_by_factor(&mut frilayer, &inv_domain, &two_inv, gamma, coset_gen.inv(), folding_factor);
+        let mut cached = Current_layer::<Fr> {
+            current_code_word: evals.clone(),
+        };
+        cached.fold_with_twiddles(&inv_twiddles, &two_inv, gamma, coset_gen.inv(), folding_factor);
+        assert_eq!(cached.current_code_word, expected);
+    }
+}
+
 #[test]
 pub fn poly_extend_poly() {
     let size: usize = 8;
@@ -298,3 +420,148 @@ pub fn commit_and_verify_random() {
     assert!(result);
     drop(tree);
 }
+
+#[test]
+pub fn fold_evals_extension_matches_base() {
+    let size: usize = 8;
+    let logsize = 3;
+    init_ntt_domain::<Fr>(1 << logsize);
+    let v = generate_random_vector::<Fr>(size);
+    let gamma = Fr::from_u32(200);
+    let rou = get_root_of_unity::<Fr>(size.try_into().unwrap()).unwrap();
+    let rou_inv = rou.inv();
+    let inv_domain: Vec<Fr> = iter::successors(Some(Fr::one()), |p| Some(*p * rou_inv))
+        .take(size / 2)
+        .collect();
+
+    let mut base_layer = Current_layer::<Fr> {
+        current_code_word: v.clone(),
+    };
+    let base_fold = base_layer.fold_evals(Fr::one(), gamma);
+
+    //a base field challenge lifted into the extension has to give the lifted base fold
+    let mut ext_layer = Current_layer::<Ext> {
+        current_code_word: lift_vec::<Fr, Ext>(&v),
+    };
+    let mut inv_domain_ext: Vec<Ext> = lift_vec::<Fr, Ext>(&inv_domain);
+    let two_inv = Ext::from_u32(2).inv();
+    let ext_fold =
+        ext_layer.fold_evals_precompute_domain(&mut inv_domain_ext, &two_inv, lift::<Fr, Ext>(&gamma));
+    assert_eq!(ext_fold, lift_vec::<Fr, Ext>(&base_fold));
+}
+
+#[test]
+pub fn batch_opening_dedups_and_verifies() {
+    let size: usize = 1 << 12;
+    let test_vec = generate_random_vector::<Fr>(size);
+    let mut current = Current_layer {
+        current_code_word: test_vec.clone(),
+    };
+    let tree: MerkleTree = current.commit();
+    let mut rng = rand::thread_rng();
+    //neighbouring indices and a repeated index share most of their paths
+    let mut indices: Vec<u64> = (0..50).map(|_| rng.gen_range(0..size as u64)).collect();
+    indices.push(indices[0]);
+    indices.push(indices[1] ^ 1);
+    let proofs: Vec<MerkleProof> = indices
+        .iter()
+        .map(|&index| current.layer_query(index, &tree))
+        .collect();
+    let commit_config = CommitConfig::default();
+    let opening = BatchOpening::from_proofs(&proofs, &commit_config.level_arities(size));
+    let individual = individual_proofs_num_bytes(&proofs);
+    println!(
+        "batch opening {:?} bytes, individual paths {:?} bytes",
+        opening.num_bytes(),
+        individual
+    );
+    assert!(opening.num_bytes() < individual);
+    let root: Vec<u8> = tree.get_root::<u8>().unwrap().to_vec();
+    assert!(opening.verify(&root, &commit_config, size, 4));
+
+    let mut forged = opening.clone();
+    forged.auth_nodes.pop();
+    assert!(!forged.verify(&root, &commit_config, size, 4));
+    drop(tree);
+}
+
+#[test]
+pub fn commit_config_level_arities() {
+    let arity_4 = CommitConfig {
+        arity: 4,
+        ..CommitConfig::default()
+    };
+    assert_eq!(arity_4.level_arities(1 << 5), vec![2, 4, 4]);
+    assert_eq!(arity_4.level_arities(1 << 4), vec![4, 4]);
+    let arity_8 = CommitConfig {
+        arity: 8,
+        ..CommitConfig::default()
+    };
+    assert_eq!(arity_8.level_arities(1 << 5), vec![4, 8]);
+    assert_eq!(arity_8.level_arities(1), Vec::<usize>::new());
+    assert!(!CommitConfig {
+        compression: HashKind::Poseidon2,
+        ..CommitConfig::default()
+    }
+    .is_valid());
+}
+
+#[test]
+pub fn batch_opening_higher_arity_verifies() {
+    //2^11 leaves, not a power of 4 or 8 so the bottom level is smaller
+    let size: usize = 1 << 11;
+    let test_vec = generate_random_vector::<Fr>(size);
+    let mut rng = rand::thread_rng();
+    let indices: Vec<u64> = (0..30).map(|_| rng.gen_range(0..size as u64)).collect();
+    for (leaf_hash, compression, arity) in [
+        (HashKind::Blake3, HashKind::Blake3, 4usize),
+        (HashKind::Keccak256, HashKind::Blake2s, 8),
+        (HashKind::Poseidon2, HashKind::Poseidon2, 4),
+    ] {
+        let commit_config = CommitConfig {
+            leaf_hash,
+            compression,
+            arity,
+        };
+        //poseidon2 needs a supported width, 4 elements per leaf
+        let elements_per_leaf = if leaf_hash == HashKind::Poseidon2 { 4 } else { 1 };
+        let num_leaves = size / elements_per_leaf;
+        let mut current = Current_layer {
+            current_code_word: test_vec.clone(),
+        };
+        let tree: MerkleTree = current.commit_with_config(&commit_config, elements_per_leaf);
+        let proofs: Vec<MerkleProof> = indices
+            .iter()
+            .map(|&index| current.layer_query(index % num_leaves as u64, &tree))
+            .collect();
+        let opening =
+            BatchOpening::from_proofs(&proofs, &commit_config.level_arities(num_leaves));
+        let leaf_size = 4 * elements_per_leaf as u64;
+        let root: Vec<u8> = tree.get_root::<u8>().unwrap().to_vec();
+        assert!(opening.verify(&root, &commit_config, num_leaves, leaf_size));
+        //the binary blake2s tree has another root
+        assert!(!opening.verify(&root, &CommitConfig::default(), num_leaves, leaf_size));
+
+        let mut forged = opening.clone();
+        forged.leaves[0][0] ^= 1;
+        assert!(!forged.verify(&root, &commit_config, num_leaves, leaf_size));
+    }
+}
+
+#[test]
+pub fn interpolate_coset_recovers_coeffs() {
+    let size: usize = 16;
+    let coeffs: Vec<Fr> = (1..=size as u32 / 4).map(Fr::from_u32).collect();
+    let coset_gen: Fr = Fr::from_u32(31);
+    let evals = coeff_to_eval_blowup_coset::<Fr>(coeffs.clone(), size, coset_gen);
+    let recovered = interpolate_coset::<Fr, Fr>(&evals, coset_gen.inv());
+    //zero above the degree
+    assert_eq!(recovered[..size / 4], coeffs[..]);
+    assert!(recovered[size / 4..].iter().all(|c| *c == Fr::zero()));
+    //and evaluates back to the codeword
+    let rou: Fr = get_root_of_unity::<Fr>(size.try_into().unwrap()).unwrap();
+    for (i, eval) in evals.iter().enumerate() {
+        let x = coset_gen * pow(rou, i as u32);
+        assert_eq!(evaluate_poly(&recovered, x), *eval);
+    }
+}
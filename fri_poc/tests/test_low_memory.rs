@@ -0,0 +1,99 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use merlin::Transcript;
+
+use fri_poc::data_structures::*;
+use fri_poc::prover::{FriProver, ProverMemory};
+use fri_poc::security::prover_peak_memory;
+use fri_poc::utils::*;
+use fri_poc::verifier::verify;
+
+mod common;
+use common::*;
+
+const HASH_BYTES: usize = 32;
+
+// cargo test --package fri_poc --test test_low_memory -- low_memory_prover_matches_full_prover --exact --show-output
+#[test]
+fn low_memory_prover_matches_full_prover() {
+    let log_size: u32 = 12;
+    let coeffs: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    for (folding_factor, leaf_layout, fri_mode, domain_offset) in [
+        (2usize, LeafLayout::Single, FriMode::Plain, 1u32),
+        (4, LeafLayout::Coset, FriMode::Plain, 31),
+        (16, LeafLayout::Coset, FriMode::Deep, 1),
+        (2, LeafLayout::Single, FriMode::Deep, 31),
+    ] {
+        let fri_config = Friconfig {
+            fri_mode,
+            ..fri_config(folding_factor, leaf_layout, domain_offset)
+        };
+        let full_prover = FriProver::<Fr, Ext>::new(fri_config, log_size);
+        let code_word: Vec<Fr> = full_prover.lde(coeffs.clone());
+        let mut full_transcript = Transcript::new(b"Low_memory_FRI");
+        let full_proof =
+            full_prover.prove(DOMAIN_SEPARATOR, PUBLIC_INPUT, &mut full_transcript, code_word.clone());
+
+        for tree_min_layer in [0u64, 3] {
+            let low_prover = FriProver::<Fr, Ext>::with_memory(
+                fri_config,
+                log_size,
+                ProverMemory::Low { tree_min_layer },
+            );
+            let mut low_transcript = Transcript::new(b"Low_memory_FRI");
+            let low_proof =
+                low_prover.prove(DOMAIN_SEPARATOR, PUBLIC_INPUT, &mut low_transcript, code_word.clone());
+
+            //same layers and the same tree roots, pruned levels are rehashed for the openings
+            assert_eq!(low_proof.layer_commitments, full_proof.layer_commitments);
+            assert_eq!(low_proof.layer_openings, full_proof.layer_openings);
+            assert_eq!(low_proof.deep_values, full_proof.deep_values);
+            assert_eq!(low_proof.final_poly, full_proof.final_poly);
+            assert_eq!(low_proof.pow_nonce, full_proof.pow_nonce);
+
+            let mut verifier_transcript = Transcript::new(b"Low_memory_FRI");
+            assert_eq!(
+                verify::<Fr, Ext>(
+                    fri_config,
+                    log_size,
+                    DOMAIN_SEPARATOR,
+                    PUBLIC_INPUT,
+                    low_proof,
+                    &mut verifier_transcript,
+                ),
+                Ok(())
+            );
+        }
+    }
+}
+
+// cargo test --package fri_poc --test test_low_memory -- peak_memory_estimate --exact --show-output
+#[test]
+fn peak_memory_estimate() {
+    for leaf_layout in [LeafLayout::Single, LeafLayout::Coset] {
+        let fri_config = fri_config(2, leaf_layout, 1);
+        let full = prover_peak_memory::<Fr, Ext>(&fri_config, 26, HASH_BYTES, ProverMemory::Full);
+        let low = prover_peak_memory::<Fr, Ext>(
+            &fri_config,
+            26,
+            HASH_BYTES,
+            ProverMemory::Low { tree_min_layer: 8 },
+        );
+        println!(
+            "2^26 {:?}: full {:?} MiB, low {:?} MiB",
+            leaf_layout,
+            full >> 20,
+            low >> 20
+        );
+        //the first layer and its twiddles in the base field are a lower bound for both
+        assert!(low >= 2 * (1 << 26) * 4);
+        assert!(4 * low < full);
+        //unpruned trees only add the tree digests
+        let unpruned = prover_peak_memory::<Fr, Ext>(
+            &fri_config,
+            26,
+            HASH_BYTES,
+            ProverMemory::Low { tree_min_layer: 0 },
+        );
+        assert!(unpruned > low);
+    }
+}
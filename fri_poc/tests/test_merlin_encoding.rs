@@ -1,6 +1,10 @@
 #[cfg(test)]
 use fri_poc::transcript::TranscriptProtocol;
-use fri_poc::utils::{hash_fuse, num_leading_zeros, proof_of_work, try_load_and_set_backend_gpu};
+use fri_poc::data_structures::HashKind;
+use fri_poc::pow::pow_digest;
+use fri_poc::utils::{
+    num_leading_zeros, proof_of_work, sample_distinct_queries, try_load_and_set_backend_gpu,
+};
 use hex::encode;
 use icicle_babybear::field::ScalarField as Fr;
 use icicle_core::{
@@ -104,7 +108,39 @@ fn test_pow() {
     let pow_bits: usize = 24;
     let nonce = proof_of_work(pow_bits, challenge);
     println!("nonce {:?} ", nonce);
-    let out: Vec<u8> = hash_fuse(challenge.to_bytes_le(), nonce.to_le_bytes().to_vec());
+    let out: Vec<u8> = pow_digest(HashKind::Blake2s, &challenge.to_bytes_le(), nonce);
     let out_lead_zeros: usize = num_leading_zeros(out);
-    assert_eq!(out_lead_zeros, pow_bits);
+    assert!(out_lead_zeros >= pow_bits);
+}
+
+#[test]
+fn full_commitment_is_absorbed() {
+    //roots that agree on their first field element still give different challenges
+    let root_a: Vec<u8> = vec![7u8; 32];
+    let mut root_b = root_a.clone();
+    root_b[31] ^= 1;
+    let mut challenges: Vec<Fr> = Vec::new();
+    for root in [root_a, root_b] {
+        let mut transcript = Transcript::new(b"test");
+        TranscriptProtocol::<Fr>::append_commitment(&mut transcript, b"commit", &root);
+        challenges.push(TranscriptProtocol::<Fr>::challenge_scalar(&mut transcript, b"challenge"));
+    }
+    assert_ne!(challenges[0], challenges[1]);
+}
+
+#[test]
+fn sampled_queries_are_distinct() {
+    //asking for every position forces duplicate draws, the result is a permutation
+    let mut transcript = Transcript::new(b"test");
+    let (query_indices, duplicates) = sample_distinct_queries::<Fr>(&mut transcript, 64, 64);
+    let mut sorted = query_indices.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..64).collect::<Vec<usize>>());
+    assert!(duplicates > 0);
+
+    //non power of two ranges are rejection sampled, not reduced
+    let mut transcript = Transcript::new(b"test");
+    for _ in 0..1000 {
+        assert!(TranscriptProtocol::<Fr>::challenge_index(&mut transcript, b"query", 3) < 3);
+    }
 }
@@ -0,0 +1,92 @@
+use icicle_babybear::field::ScalarField as Fr;
+use icicle_core::{ntt::get_root_of_unity, traits::Arithmetic};
+use merlin::Transcript;
+
+use fri_poc::data_structures::*;
+use fri_poc::mmcs::*;
+use fri_poc::transcript::TranscriptProtocol;
+use fri_poc::utils::*;
+
+const ELEMENT_SIZE: usize = 4;
+
+fn matrices() -> Vec<Matrix<Fr>> {
+    [(1usize << 10, 3usize), (1 << 6, 1), (1 << 10, 2), (1 << 3, 5)]
+        .iter()
+        .map(|&(height, width)| Matrix::new(generate_random_vector::<Fr>(height * width), width))
+        .collect()
+}
+
+fn dims(matrices: &[Matrix<Fr>]) -> Vec<(usize, usize)> {
+    matrices.iter().map(|matrix| (matrix.height(), matrix.width)).collect()
+}
+
+// cargo test --package fri_poc --test test_mmcs -- mixed_heights_open_and_verify --exact --show-output
+#[test]
+fn mixed_heights_open_and_verify() {
+    let matrices = matrices();
+    let dims = dims(&matrices);
+    for commit_config in [
+        CommitConfig::default(),
+        CommitConfig {
+            leaf_hash: HashKind::Keccak256,
+            compression: HashKind::Keccak256,
+            arity: 2,
+        },
+    ] {
+        let (root, prover_data) = commit(&commit_config, matrices.clone());
+        //indices 0 and 512 share a row of every smaller matrix, row i mod h
+        let opening = prover_data.open(&[0, 512, 517, 1023]);
+        println!("{:?}: opening {:?} bytes", commit_config, opening.num_bytes());
+        assert!(opening.verify(&root, &commit_config, &dims, ELEMENT_SIZE));
+
+        //a row of an injected matrix is bound to the root
+        let mut forged = opening.clone();
+        forged.rows[2][3][0] ^= 1;
+        assert!(!forged.verify(&root, &commit_config, &dims, ELEMENT_SIZE));
+
+        //two leaves sharing a row have to agree on it
+        let mut forged = opening.clone();
+        forged.rows[1][1] = opening.rows[3][1].clone();
+        assert!(!forged.verify(&root, &commit_config, &dims, ELEMENT_SIZE));
+
+        //the verifier has to know the heights
+        let mut wrong_dims = dims.clone();
+        wrong_dims[1].0 = 1 << 7;
+        assert!(!opening.verify(&root, &commit_config, &wrong_dims, ELEMENT_SIZE));
+    }
+}
+
+#[test]
+fn trace_columns_of_different_lengths() {
+    //LDEs of columns with 2^8 and 2^6 coefficients committed in one tree, opened at
+    //query positions squeezed from the transcript after the root
+    let blow_up_factor = 4;
+    let long: Vec<Vec<Fr>> = (0..3)
+        .map(|_| coeff_to_eval_blowup::<Fr>(generate_random_vector::<Fr>(1 << 8), blow_up_factor << 8))
+        .collect();
+    let short_coeffs: Vec<Vec<Fr>> = (0..2).map(|_| generate_random_vector::<Fr>(1 << 6)).collect();
+    let short: Vec<Vec<Fr>> = short_coeffs
+        .iter()
+        .map(|coeffs| coeff_to_eval_blowup::<Fr>(coeffs.clone(), blow_up_factor << 6))
+        .collect();
+    let matrices = vec![Matrix::from_columns(&long), Matrix::from_columns(&short)];
+    let dims = dims(&matrices);
+    let commit_config = CommitConfig::default();
+    let (root, prover_data) = commit(&commit_config, matrices);
+
+    let mut prover_transcript = Transcript::new(b"MMCS");
+    <Transcript as TranscriptProtocol<Fr>>::append_commitment(&mut prover_transcript, b"commit", &root);
+    let (query_indices, _) = sample_distinct_queries::<Fr>(&mut prover_transcript, 20, blow_up_factor << 8);
+    let opening = prover_data.open(&query_indices);
+    assert!(opening.verify(&root, &commit_config, &dims, ELEMENT_SIZE));
+
+    //the opened short row is the LDE value at the folded point x^(H/h), x the query's point
+    let rou: Fr = get_root_of_unity::<Fr>((blow_up_factor << 8) as u64).unwrap();
+    for (&query, rows) in query_indices.iter().zip(opening.rows.iter()) {
+        let folded_point = pow(pow(rou, query as u32), 4);
+        for (c, coeffs) in short_coeffs.iter().enumerate() {
+            let opened = Fr::from_bytes_le(&rows[1][c * ELEMENT_SIZE..(c + 1) * ELEMENT_SIZE]);
+            assert_eq!(opened, evaluate_poly(coeffs, folded_point));
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use icicle_core::traits::Arithmetic;
+use merlin::Transcript;
+
+use fri_poc::data_structures::*;
+use fri_poc::error::FriError;
+use fri_poc::pcs::*;
+use fri_poc::utils::*;
+
+const FRI_CONFIG: Friconfig = Friconfig {
+    blow_up_factor: 4,
+    folding_factor: 2,
+    pow_bits: 10,
+    pow_hash: HashKind::Blake2s,
+    num_queries: 30,
+    stopping_size: 16,
+    leaf_layout: LeafLayout::Single,
+    fri_mode: FriMode::Plain,
+    zk: false,
+    domain_offset: 31,
+    commit_config: CommitConfig::default(),
+};
+const LOG_DOMAIN_SIZE: u32 = 12;
+
+fn verify(commitment: &[u8], opening: &PcsOpening<Ext>) -> Result<(), FriError> {
+    let mut verifier_transcript = Transcript::new(b"PCS_FRI");
+    verify_opening::<Fr, Ext>(
+        FRI_CONFIG,
+        LOG_DOMAIN_SIZE,
+        commitment,
+        opening,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut verifier_transcript,
+    )
+}
+
+// cargo test --package fri_poc --test test_pcs -- pcs_opening_verifies --exact --show-output
+#[test]
+fn pcs_opening_verifies() {
+    let coeffs: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    let (commitment, mut prover_data) = commit::<Fr>(&FRI_CONFIG, coeffs.clone());
+    let mut prover_transcript = Transcript::new(b"PCS_FRI");
+    let opening: PcsOpening<Ext> = open::<Fr, Ext>(
+        FRI_CONFIG,
+        &mut prover_data,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+    );
+    println!("opening proof {:?} bytes", opening.num_bytes());
+    assert_eq!(
+        opening.value,
+        evaluate_poly(&lift_vec::<Fr, Ext>(&coeffs), opening.point)
+    );
+    assert_eq!(verify(&commitment, &opening), Ok(()));
+}
+
+#[test]
+fn wrong_value_or_commitment_is_rejected() {
+    let coeffs: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    let (commitment, mut prover_data) = commit::<Fr>(&FRI_CONFIG, coeffs);
+    let mut prover_transcript = Transcript::new(b"PCS_FRI");
+    let opening: PcsOpening<Ext> = open::<Fr, Ext>(
+        FRI_CONFIG,
+        &mut prover_data,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+    );
+
+    //the quotient of a wrong value has a pole at z, it is not low degree
+    let mut wrong_value = opening.clone();
+    wrong_value.value = wrong_value.value + Ext::one();
+    assert!(verify(&commitment, &wrong_value).is_err());
+
+    //another polynomial's commitment gives another point and fails the merkle check
+    let (other_commitment, _) = commit::<Fr>(&FRI_CONFIG, generate_random_vector::<Fr>(1 << 10));
+    assert_eq!(
+        verify(&other_commitment, &opening),
+        Err(FriError::MerklePath {
+            layer: 0,
+            leaf: opening.codeword_opening.leaf_indices[0]
+        })
+    );
+}
+
+// cargo test --package fri_poc --test test_pcs -- one_coefficient_too_many_is_rejected --exact --show-output
+#[test]
+fn one_coefficient_too_many_is_rejected() {
+    //D + 1 coefficients committed on the domain of D coefficients, commit would pick a larger domain
+    let size: usize = 1 << LOG_DOMAIN_SIZE;
+    let coeffs: Vec<Fr> = generate_random_vector::<Fr>(size / FRI_CONFIG.blow_up_factor + 1);
+    let mut code_word: Current_layer<Fr> = Current_layer::new();
+    code_word.current_code_word =
+        coeff_to_eval_blowup_coset::<Fr>(coeffs.clone(), size, Fr::from_u32(FRI_CONFIG.domain_offset));
+    let tree = code_word.commit_with_config(&FRI_CONFIG.commit_config, 1);
+    let commitment: Vec<u8> = tree.get_root::<u8>().unwrap().to_vec();
+    let mut prover_data = PcsProverData {
+        coeffs,
+        code_word,
+        tree,
+    };
+    let mut prover_transcript = Transcript::new(b"PCS_FRI");
+    let opening: PcsOpening<Ext> = open::<Fr, Ext>(
+        FRI_CONFIG,
+        &mut prover_data,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+    );
+    //the value is right, the quotient has D coefficients and its correction D + 1
+    assert!(verify(&commitment, &opening).is_err());
+}
@@ -0,0 +1,104 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use icicle_core::{bignum::BigNum, traits::Arithmetic};
+use merlin::Transcript;
+
+use fri_poc::data_structures::*;
+use fri_poc::pow::*;
+use fri_poc::prover::*;
+use fri_poc::utils::*;
+use fri_poc::verifier::verify;
+
+const POW_HASHES: [HashKind; 4] = [
+    HashKind::Blake2s,
+    HashKind::Blake3,
+    HashKind::Keccak256,
+    HashKind::Poseidon2,
+];
+
+// cargo test --package fri_poc --test test_pow -- grind_finds_smallest_nonce --exact --show-output
+#[test]
+fn grind_finds_smallest_nonce() {
+    let challenge = Fr::from_u32(99).to_bytes_le();
+    let pow_bits = 8;
+    for pow_hash in POW_HASHES {
+        let nonce = grind(pow_hash, pow_bits, &challenge);
+        println!("{:?} nonce {:?}", pow_hash, nonce);
+        assert!(verify_pow(pow_hash, pow_bits, &challenge, nonce));
+        //threshold, fewer bits are accepted too
+        assert!(verify_pow(pow_hash, pow_bits - 1, &challenge, nonce));
+        //deterministic regardless of how the batches were scheduled
+        assert_eq!(grind(pow_hash, pow_bits, &challenge), nonce);
+        assert!((0..nonce).all(|n| !verify_pow(pow_hash, pow_bits, &challenge, n)));
+    }
+}
+
+#[test]
+fn grind_crosses_batches() {
+    //20 bits needs about 2^20 attempts, well past the first batch
+    let challenge = Fr::from_u32(7).to_bytes_le();
+    let nonce = grind(HashKind::Blake3, 20, &challenge);
+    assert!(verify_pow(HashKind::Blake3, 20, &challenge, nonce));
+    assert!(num_leading_zeros(pow_digest(HashKind::Blake3, &challenge, nonce)) >= 20);
+}
+
+#[test]
+fn e2e_fri_pow_hash_test() {
+    //deterministic input, so the wrong hash check below is reproducible
+    let input_data: Vec<Fr> = (0..256u32).map(Fr::from_u32).collect();
+    for pow_hash in POW_HASHES {
+        let fri_config: Friconfig = Friconfig {
+            blow_up_factor: 4,
+            folding_factor: 2,
+            pow_bits: 12,
+            pow_hash,
+            num_queries: 20,
+            stopping_size: 16,
+            leaf_layout: LeafLayout::Single,
+            fri_mode: FriMode::Plain,
+            zk: false,
+            domain_offset: 1,
+            commit_config: CommitConfig::default(),
+        };
+        let size: usize = input_data.len() * fri_config.blow_up_factor;
+        let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data.clone(), size);
+        let mut prover_transcript = Transcript::new(b"Pow_FRI");
+        let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+            fri_config,
+            b"my_Shitty_FRI",
+            b"public",
+            &mut prover_transcript,
+            code_word,
+        );
+
+        let mut verifier_transcript = Transcript::new(b"Pow_FRI");
+        verify::<Fr, Ext>(
+            fri_config,
+            size.ilog2(),
+            b"my_Shitty_FRI",
+            b"public",
+            friproof.clone(),
+            &mut verifier_transcript,
+        )
+        .unwrap();
+
+        //a verifier expecting another hash does not accept the nonce
+        let other = Friconfig {
+            pow_hash: if pow_hash == HashKind::Blake2s {
+                HashKind::Keccak256
+            } else {
+                HashKind::Blake2s
+            },
+            ..fri_config
+        };
+        let mut verifier_transcript = Transcript::new(b"Pow_FRI");
+        assert!(verify::<Fr, Ext>(
+            other,
+            size.ilog2(),
+            b"my_Shitty_FRI",
+            b"public",
+            friproof,
+            &mut verifier_transcript,
+        )
+        .is_err());
+    }
+}
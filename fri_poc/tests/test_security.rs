@@ -0,0 +1,90 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use merlin::Transcript;
+
+use fri_poc::data_structures::*;
+use fri_poc::prover::*;
+use fri_poc::security::*;
+use fri_poc::utils::*;
+
+//blake2s digests
+const HASH_BYTES: usize = 32;
+
+#[test]
+fn babybear_extension_field_bits() {
+    //p = 15*2^27+1, degree 4
+    let bits = field_bits::<Fr, Ext>();
+    assert!((bits - 4.0 * 2013265921f64.log2()).abs() < 1e-6);
+}
+
+// cargo test --package fri_poc --test test_security -- for_security_reaches_target --exact --show-output
+#[test]
+fn for_security_reaches_target() {
+    for (bits, blow_up_factor) in [(80usize, 2usize), (100, 4), (100, 8), (96, 16)] {
+        let fri_config = Friconfig::for_security(bits, blow_up_factor);
+        //2^20 keeps the commit phase below 2^-100 in the 124 bit extension
+        let report = fri_config.security_report::<Fr, Ext>(20, HASH_BYTES);
+        println!("{:?} bits, blowup {:?}: {:?} {:?}", bits, blow_up_factor, fri_config, report);
+        assert!(report.conjectured_bits >= bits as f64);
+        //one query less does not
+        let fewer = Friconfig {
+            num_queries: fri_config.num_queries - 1,
+            ..fri_config
+        };
+        assert!(fewer.security_report::<Fr, Ext>(20, HASH_BYTES).conjectured_bits < bits as f64);
+    }
+}
+
+#[test]
+fn provable_bits_below_conjectured() {
+    let fri_config = Friconfig::for_security(100, 4);
+    for log_domain_size in [12u32, 16, 20] {
+        let report = fri_config.security_report::<Fr, Ext>(log_domain_size, HASH_BYTES);
+        assert!(report.unique_decoding_bits <= report.johnson_bits);
+        assert!(report.johnson_bits <= report.conjectured_bits);
+        //more queries never hurt
+        let more = Friconfig {
+            num_queries: 2 * fri_config.num_queries,
+            ..fri_config
+        };
+        let more_report = more.security_report::<Fr, Ext>(log_domain_size, HASH_BYTES);
+        assert!(more_report.johnson_bits >= report.johnson_bits);
+        assert!(more_report.proof_size_bytes > report.proof_size_bytes);
+    }
+}
+
+// cargo test --package fri_poc --test test_security -- proof_size_estimate_matches_proof --exact --show-output
+#[test]
+fn proof_size_estimate_matches_proof() {
+    for leaf_layout in [LeafLayout::Single, LeafLayout::Coset] {
+        let fri_config: Friconfig = Friconfig {
+            blow_up_factor: 4,
+            folding_factor: 4,
+            pow_bits: 10,
+            pow_hash: HashKind::Blake2s,
+            num_queries: 50,
+            stopping_size: 16,
+            leaf_layout,
+            fri_mode: FriMode::Plain,
+            zk: false,
+            domain_offset: 1,
+            commit_config: CommitConfig::default(),
+        };
+        let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+        let size: usize = input_data.len() * fri_config.blow_up_factor;
+        let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data, size);
+        let mut prover_transcript = Transcript::new(b"Security_FRI");
+        let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+            fri_config,
+            b"my_Shitty_FRI",
+            b"public",
+            &mut prover_transcript,
+            code_word,
+        );
+
+        let estimate = expected_proof_size::<Fr, Ext>(&fri_config, size.ilog2(), HASH_BYTES);
+        let actual = friproof.num_bytes();
+        println!("{:?}: estimate {:?} bytes, proof {:?} bytes", leaf_layout, estimate, actual);
+        //query positions are random, the estimate is an expectation
+        assert!(estimate.abs_diff(actual) * 10 < actual);
+    }
+}
@@ -0,0 +1,165 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use icicle_core::traits::Arithmetic;
+use merlin::Transcript;
+
+use fri_poc::error::FriError;
+use fri_poc::pcs::*;
+use fri_poc::prover::*;
+use fri_poc::serialization::FriproofBundle;
+use fri_poc::utils::*;
+use fri_poc::{data_structures::*, verifier::verify};
+
+fn proof_bytes(fri_config: Friconfig) -> Vec<u8> {
+    let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    let size: usize = input_data.len() * fri_config.blow_up_factor;
+    let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data, size);
+    let mut prover_transcript = Transcript::new(b"Serialized_FRI");
+    let friproof: Friproof<Ext> = prove::<Fr, Ext>(
+        fri_config,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        code_word,
+    );
+    friproof.to_bytes::<Fr>(&fri_config, size.ilog2())
+}
+
+// cargo test --package fri_poc --test test_serialization -- serialize_roundtrip_verifies --exact --show-output
+#[test]
+fn serialize_roundtrip_verifies() {
+    let fri_config: Friconfig = Friconfig {
+        blow_up_factor: 4,
+        folding_factor: 4,
+        pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
+        num_queries: 20,
+        stopping_size: 16,
+        leaf_layout: LeafLayout::Coset,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset: 1,
+        commit_config: CommitConfig::default(),
+    };
+    let bytes = proof_bytes(fri_config);
+    let log_domain_size: u32 = 10 + fri_config.blow_up_factor.ilog2();
+    println!("serialized proof size {:?} bytes", bytes.len());
+
+    //the verifier only sees bytes, merkle proofs are rebuilt from the path data
+    let (header_config, header_log_domain_size, friproof) =
+        Friproof::<Ext>::from_bytes::<Fr>(&bytes).unwrap();
+    assert_eq!(header_config, fri_config);
+    assert_eq!(header_log_domain_size, log_domain_size);
+    assert_eq!(friproof.to_bytes::<Fr>(&fri_config, log_domain_size), bytes);
+    let mut verifier_transcript = Transcript::new(b"Serialized_FRI");
+    verify::<Fr, Ext>(
+        fri_config,
+        log_domain_size,
+        b"my_Shitty_FRI",
+        b"public",
+        friproof,
+        &mut verifier_transcript,
+    )
+    .unwrap();
+
+    let bundle = FriproofBundle::<Fr, Ext>::from_bytes(&bytes).unwrap();
+    assert_eq!(bundle.to_bytes(), bytes);
+}
+
+#[test]
+fn corrupted_bytes_are_rejected() {
+    let fri_config: Friconfig = Friconfig {
+        blow_up_factor: 4,
+        folding_factor: 2,
+        pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
+        num_queries: 10,
+        stopping_size: 16,
+        leaf_layout: LeafLayout::Single,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset: 1,
+        commit_config: CommitConfig::default(),
+    };
+    let bytes = proof_bytes(fri_config);
+
+    let truncated = &bytes[..bytes.len() - 1];
+    assert!(matches!(
+        Friproof::<Ext>::from_bytes::<Fr>(truncated),
+        Err(FriError::MalformedProof(_))
+    ));
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] ^= 1;
+    assert!(Friproof::<Ext>::from_bytes::<Fr>(&bad_magic).is_err());
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(Friproof::<Ext>::from_bytes::<Fr>(&trailing).is_err());
+
+    //base field proofs cannot be read back as extension proofs
+    assert!(Friproof::<Fr>::from_bytes::<Fr>(&bytes).is_err());
+
+    //num layer openings sits after the header and the commitments, u32::MAX must not overflow
+    let element_size = Fr::one().to_bytes_le().len();
+    let mut pos = 4 + 2 + 4 + element_size + 4 + fri_config.to_bytes().len() + 4;
+    let num_commitments = u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap());
+    pos += 4;
+    for _ in 0..num_commitments {
+        pos += 4 + u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+    }
+    let mut max_rounds = bytes.clone();
+    max_rounds[pos..pos + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(Friproof::<Ext>::from_bytes::<Fr>(&max_rounds).is_err());
+}
+
+// cargo test --package fri_poc --test test_serialization -- pcs_proof_roundtrip_verifies --exact --show-output
+#[test]
+fn pcs_proof_roundtrip_verifies() {
+    //the first layer of a pcs proof is the committed polynomial, the proof has one opening less
+    let fri_config: Friconfig = Friconfig {
+        blow_up_factor: 4,
+        folding_factor: 2,
+        pow_bits: 10,
+        pow_hash: HashKind::Blake2s,
+        num_queries: 20,
+        stopping_size: 16,
+        leaf_layout: LeafLayout::Single,
+        fri_mode: FriMode::Plain,
+        zk: false,
+        domain_offset: 31,
+        commit_config: CommitConfig::default(),
+    };
+    let log_domain_size: u32 = 10 + fri_config.blow_up_factor.ilog2();
+    let (commitment, mut prover_data) = commit::<Fr>(&fri_config, generate_random_vector::<Fr>(1 << 10));
+    let mut prover_transcript = Transcript::new(b"Serialized_PCS");
+    let mut opening: PcsOpening<Ext> = open::<Fr, Ext>(
+        fri_config,
+        &mut prover_data,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+    );
+
+    let bytes = opening.fri_proof.to_bytes::<Fr>(&fri_config, log_domain_size);
+    let (header_config, header_log_domain_size, friproof) =
+        Friproof::<Ext>::from_bytes::<Fr>(&bytes).unwrap();
+    assert_eq!(header_config, fri_config);
+    assert_eq!(header_log_domain_size, log_domain_size);
+    assert_eq!(friproof.to_bytes::<Fr>(&fri_config, log_domain_size), bytes);
+    opening.fri_proof = friproof;
+    let mut verifier_transcript = Transcript::new(b"Serialized_PCS");
+    verify_opening::<Fr, Ext>(
+        fri_config,
+        log_domain_size,
+        &commitment,
+        &opening,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut verifier_transcript,
+    )
+    .unwrap();
+
+    //a domain size the proof shape does not fit is rejected
+    let wrong_size = opening.fri_proof.to_bytes::<Fr>(&fri_config, log_domain_size + 2);
+    assert!(Friproof::<Ext>::from_bytes::<Fr>(&wrong_size).is_err());
+}
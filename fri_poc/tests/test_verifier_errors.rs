@@ -0,0 +1,359 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use icicle_core::{merkle::MerkleProof, traits::Arithmetic};
+use merlin::Transcript;
+
+use fri_poc::error::FriError;
+use fri_poc::prover::*;
+use fri_poc::utils::*;
+use fri_poc::{data_structures::*, verifier::verify};
+
+const FRI_CONFIG: Friconfig = Friconfig {
+    blow_up_factor: 4,
+    folding_factor: 2,
+    pow_bits: 10,
+    pow_hash: HashKind::Blake2s,
+    num_queries: 8,
+    stopping_size: 4,
+    leaf_layout: LeafLayout::Single,
+    fri_mode: FriMode::Plain,
+    zk: false,
+    domain_offset: 1,
+    commit_config: CommitConfig::default(),
+};
+
+//256 coefficients with blow up 4
+const LOG_DOMAIN_SIZE: u32 = 10;
+
+//deterministic input so the transcript and hence the checks are reproducible
+fn honest_code_word() -> Vec<Fr> {
+    let input_data: Vec<Fr> = (0..256u32).map(Fr::from_u32).collect();
+    let size: usize = input_data.len() * FRI_CONFIG.blow_up_factor;
+    coeff_to_eval_blowup::<Fr>(input_data, size)
+}
+
+fn honest_proof() -> Friproof<Ext> {
+    let mut prover_transcript = Transcript::new(b"Adversarial_FRI");
+    prove::<Fr, Ext>(
+        FRI_CONFIG,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        honest_code_word(),
+    )
+}
+
+fn verify_proof(friproof: Friproof<Ext>) -> Result<(), FriError> {
+    let mut verifier_transcript = Transcript::new(b"Adversarial_FRI");
+    verify::<Fr, Ext>(
+        FRI_CONFIG,
+        LOG_DOMAIN_SIZE,
+        b"my_Shitty_FRI",
+        b"public",
+        friproof,
+        &mut verifier_transcript,
+    )
+}
+
+#[test]
+fn honest_proof_verifies() {
+    assert_eq!(verify_proof(honest_proof()), Ok(()));
+}
+
+#[test]
+fn tampered_final_poly_is_rejected() {
+    let mut friproof = honest_proof();
+    for value in friproof.final_poly.iter_mut() {
+        *value = *value + Ext::one();
+    }
+    assert!(matches!(
+        verify_proof(friproof),
+        Err(FriError::FinalPoly { .. })
+    ));
+}
+
+#[test]
+fn final_poly_above_degree_bound_is_rejected() {
+    //an extra zero coefficient evaluates the same but breaks the degree bound
+    let mut friproof = honest_proof();
+    friproof.final_poly.push(Ext::zero());
+    assert_eq!(
+        verify_proof(friproof),
+        Err(FriError::FinalPolyDegree {
+            coeffs: FRI_CONFIG.final_poly_len() + 1,
+            max_coeffs: FRI_CONFIG.final_poly_len(),
+        })
+    );
+}
+
+#[test]
+fn high_degree_codeword_is_rejected() {
+    //every fold is honest, but the last layer is not low degree so its truncation disagrees
+    let size: usize = 256 * FRI_CONFIG.blow_up_factor;
+    let code_word: Vec<Fr> = (0..size as u32).map(|i| Fr::from_u32(i * i + 1)).collect();
+    let mut prover_transcript = Transcript::new(b"Adversarial_FRI");
+    let friproof = prove::<Fr, Ext>(
+        FRI_CONFIG,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        code_word,
+    );
+    assert!(matches!(
+        verify_proof(friproof),
+        Err(FriError::FinalPoly { .. })
+    ));
+}
+
+#[test]
+fn tampered_nonce_is_rejected() {
+    let mut friproof = honest_proof();
+    friproof.pow_nonce = friproof.pow_nonce.wrapping_add(1);
+    //changing the nonce changes the query indices too, but pow is checked first
+    assert_eq!(verify_proof(friproof), Err(FriError::ProofOfWork));
+}
+
+#[test]
+fn malformed_proofs_are_rejected() {
+    let mut friproof = honest_proof();
+    friproof.layer_openings.clear();
+    assert!(matches!(
+        verify_proof(friproof),
+        Err(FriError::MalformedProof(_))
+    ));
+
+    let mut friproof = honest_proof();
+    friproof.layer_openings[0].leaves.pop();
+    assert!(matches!(
+        verify_proof(friproof),
+        Err(FriError::MalformedProof(_))
+    ));
+
+    let mut friproof = honest_proof();
+    friproof.final_poly.pop();
+    assert!(matches!(
+        verify_proof(friproof),
+        Err(FriError::MalformedProof(_))
+    ));
+}
+
+#[test]
+fn swapped_queries_are_rejected() {
+    //answer the first query with the openings of the second one and vice versa
+    let mut friproof = honest_proof();
+    let k = FRI_CONFIG.folding_factor;
+    for opening in friproof.layer_openings.iter_mut() {
+        for i in 0..k {
+            opening.leaf_indices.swap(i, k + i);
+            opening.leaves.swap(i, k + i);
+        }
+    }
+    assert!(matches!(
+        verify_proof(friproof),
+        Err(FriError::QueryIndexMismatch {
+            query: 0,
+            round: 0,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn shifted_layer_indices_are_rejected() {
+    //answer query 0 in layer 1 with the openings of a query at another position, the set of
+    //opened leaves is unchanged so the layer still authenticates, and layer 0 stays honest
+    let mut friproof = honest_proof();
+    let k = FRI_CONFIG.folding_factor;
+    let opening = &mut friproof.layer_openings[1];
+    let other = (1..FRI_CONFIG.num_queries)
+        .find(|&q| opening.leaf_indices[q * k] != opening.leaf_indices[0])
+        .unwrap();
+    let (expected, found) = (opening.leaf_indices[0], opening.leaf_indices[other * k]);
+    for i in 0..k {
+        opening.leaf_indices.swap(i, other * k + i);
+        opening.leaves.swap(i, other * k + i);
+    }
+    assert_eq!(
+        verify_proof(friproof),
+        Err(FriError::QueryIndexMismatch {
+            query: 0,
+            round: 1,
+            expected: expected as usize,
+            found: found as usize,
+        })
+    );
+}
+
+#[test]
+fn swapped_siblings_are_rejected() {
+    //the two halves of the coset of query 0 in layer 1 swapped, same leaves so same root
+    let mut friproof = honest_proof();
+    let opening = &mut friproof.layer_openings[1];
+    let (index, sibling) = (opening.leaf_indices[0], opening.leaf_indices[1]);
+    opening.leaf_indices.swap(0, 1);
+    opening.leaves.swap(0, 1);
+    assert_eq!(
+        verify_proof(friproof),
+        Err(FriError::QueryIndexMismatch {
+            query: 0,
+            round: 1,
+            expected: index as usize,
+            found: sibling as usize,
+        })
+    );
+}
+
+#[test]
+fn tampered_commitment_is_rejected() {
+    //the last byte of a root is absorbed too, so the challenges and pow change
+    let mut friproof = honest_proof();
+    let last = friproof.layer_commitments[1].len() - 1;
+    friproof.layer_commitments[1][last] ^= 1;
+    assert!(verify_proof(friproof).is_err());
+
+    let mut friproof = honest_proof();
+    friproof.layer_commitments.pop();
+    assert!(matches!(
+        verify_proof(friproof),
+        Err(FriError::MalformedProof(_))
+    ));
+}
+
+#[test]
+fn query_from_other_tree_is_rejected() {
+    //answer the first query of layer 0 from a tree over another codeword, every other query
+    //is opened honestly and the layer commitment is unchanged
+    let mut friproof = honest_proof();
+    let code_word = honest_code_word();
+    let num_leaves = code_word.len();
+    let k = FRI_CONFIG.folding_factor;
+    let leaf_indices = friproof.layer_openings[0].leaf_indices.clone();
+    let mut other_code_word = code_word.clone();
+    for &index in leaf_indices[..k].iter() {
+        other_code_word[index as usize] = other_code_word[index as usize] + Fr::one();
+    }
+    //single leaf layout, layer 0 is committed in codeword order
+    let mut honest_layer = Current_layer {
+        current_code_word: code_word,
+    };
+    let mut other_layer = Current_layer {
+        current_code_word: other_code_word,
+    };
+    let honest_tree = honest_layer.commit();
+    let other_tree = other_layer.commit();
+    let arities = FRI_CONFIG.commit_config.level_arities(num_leaves);
+
+    let honest_proofs: Vec<MerkleProof> = leaf_indices
+        .iter()
+        .map(|&index| honest_layer.layer_query(index, &honest_tree))
+        .collect();
+    assert_eq!(
+        BatchOpening::from_proofs(&honest_proofs, &arities),
+        friproof.layer_openings[0]
+    );
+
+    let mixed_proofs: Vec<MerkleProof> = leaf_indices
+        .iter()
+        .enumerate()
+        .map(|(i, &index)| {
+            if i < k {
+                other_layer.layer_query(index, &other_tree)
+            } else {
+                honest_layer.layer_query(index, &honest_tree)
+            }
+        })
+        .collect();
+    friproof.layer_openings[0] = BatchOpening::from_proofs(&mixed_proofs, &arities);
+    assert_eq!(
+        verify_proof(friproof),
+        Err(FriError::MerklePath {
+            layer: 0,
+            leaf: leaf_indices[0]
+        })
+    );
+}
+
+#[test]
+fn tampered_leaf_is_rejected() {
+    //a wrong root fails the whole batch, the first opened leaf is reported
+    let mut friproof = honest_proof();
+    friproof.layer_openings[1].leaves[0][0] ^= 1;
+    let leaf = friproof.layer_openings[1].leaf_indices[0];
+    assert_eq!(verify_proof(friproof), Err(FriError::MerklePath { layer: 1, leaf }));
+
+    let mut friproof = honest_proof();
+    friproof.layer_openings[0].auth_nodes[0][0] ^= 1;
+    let leaf = friproof.layer_openings[0].leaf_indices[0];
+    assert_eq!(verify_proof(friproof), Err(FriError::MerklePath { layer: 0, leaf }));
+
+    //a leaf of the wrong size is reported itself
+    let mut friproof = honest_proof();
+    friproof.layer_openings[1].leaves[3].push(0);
+    let leaf = friproof.layer_openings[1].leaf_indices[3];
+    assert_eq!(verify_proof(friproof), Err(FriError::MerklePath { layer: 1, leaf }));
+}
+
+#[test]
+fn proof_is_bound_to_statement() {
+    let friproof = honest_proof();
+    let verify_with = |fri_config: Friconfig, domain_separator: &[u8], public_input: &[u8]| {
+        let mut verifier_transcript = Transcript::new(b"Adversarial_FRI");
+        verify::<Fr, Ext>(
+            fri_config,
+            LOG_DOMAIN_SIZE,
+            domain_separator,
+            public_input,
+            friproof.clone(),
+            &mut verifier_transcript,
+        )
+    };
+    assert!(verify_with(FRI_CONFIG, b"other_protocol", b"public").is_err());
+    assert!(verify_with(FRI_CONFIG, b"my_Shitty_FRI", b"other public").is_err());
+    //a weaker config passes every check on its own, it fails because it is absorbed
+    let weaker = Friconfig {
+        pow_bits: FRI_CONFIG.pow_bits - 1,
+        ..FRI_CONFIG
+    };
+    assert!(verify_with(weaker, b"my_Shitty_FRI", b"public").is_err());
+}
+
+#[test]
+fn domain_size_is_verifier_input() {
+    //a proof for a larger domain (higher degree) does not pass as a proof for the claimed one
+    let input_data: Vec<Fr> = (0..1024u32).map(Fr::from_u32).collect();
+    let size: usize = input_data.len() * FRI_CONFIG.blow_up_factor;
+    let mut prover_transcript = Transcript::new(b"Adversarial_FRI");
+    let larger = prove::<Fr, Ext>(
+        FRI_CONFIG,
+        b"my_Shitty_FRI",
+        b"public",
+        &mut prover_transcript,
+        coeff_to_eval_blowup::<Fr>(input_data, size),
+    );
+    assert_eq!(
+        verify_proof(larger),
+        Err(FriError::MalformedProof("one layer opening per round expected"))
+    );
+
+    //neither does a proof with layers dropped
+    let mut friproof = honest_proof();
+    friproof.layer_commitments.pop();
+    friproof.layer_openings.pop();
+    assert_eq!(
+        verify_proof(friproof),
+        Err(FriError::MalformedProof("one layer opening per round expected"))
+    );
+
+    //and the claimed size has to fit the config
+    let mut verifier_transcript = Transcript::new(b"Adversarial_FRI");
+    assert!(matches!(
+        verify::<Fr, Ext>(
+            FRI_CONFIG,
+            FRI_CONFIG.stopping_size.ilog2(),
+            b"my_Shitty_FRI",
+            b"public",
+            honest_proof(),
+            &mut verifier_transcript,
+        ),
+        Err(FriError::MalformedProof(_))
+    ));
+}
@@ -0,0 +1,108 @@
+use icicle_babybear::field::{ExtensionField as Ext, ScalarField as Fr};
+use icicle_core::traits::Arithmetic;
+
+use fri_poc::data_structures::*;
+use fri_poc::error::FriError;
+use fri_poc::utils::*;
+
+mod common;
+use common::*;
+
+const LABEL: &[u8] = b"Zk_FRI";
+
+fn zk_config(folding_factor: usize, leaf_layout: LeafLayout) -> Friconfig {
+    Friconfig {
+        zk: true,
+        num_queries: 30,
+        ..fri_config(folding_factor, leaf_layout, 1)
+    }
+}
+
+//no opened first layer value is the input's value at its position, the leaves hold input + mask
+fn assert_input_not_opened(fri_config: Friconfig, code_word: &[Fr], friproof: &Friproof<Ext>) {
+    let opening = &friproof.layer_openings[0];
+    let coset_stride = code_word.len() / fri_config.folding_factor;
+    for (&leaf_index, leaf) in opening.leaf_indices.iter().zip(opening.leaves.iter()) {
+        for e in 0..fri_config.elements_per_leaf() {
+            //Coset: leaf i holds i + e*n/k
+            let position = leaf_index as usize + e * coset_stride;
+            let opened = Fr::from_bytes_le(&leaf[4 * e..4 * (e + 1)]);
+            assert_ne!(opened, code_word[position]);
+        }
+    }
+}
+
+// cargo test --package fri_poc --test test_zk -- zk_proof_verifies --exact --show-output
+#[test]
+fn zk_proof_verifies() {
+    let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    for (folding_factor, leaf_layout) in [(2usize, LeafLayout::Single), (4, LeafLayout::Coset)] {
+        let fri_config = zk_config(folding_factor, leaf_layout);
+        let size: usize = input_data.len() * fri_config.blow_up_factor;
+        let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data.clone(), size);
+        let friproof = prove_fri(LABEL, fri_config, code_word.clone());
+        assert_input_not_opened(fri_config, &code_word, &friproof);
+        println!(
+            "folding factor {:?}, {:?}: zk proof {:?} bytes",
+            folding_factor,
+            leaf_layout,
+            friproof.num_bytes()
+        );
+        assert_eq!(verify_fri(LABEL, fri_config, size.ilog2(), friproof), Ok(()));
+    }
+}
+
+// cargo test --package fri_poc --test test_zk -- zk_proofs_of_same_poly_differ --exact --show-output
+#[test]
+fn zk_proofs_of_same_poly_differ() {
+    let fri_config = zk_config(4, LeafLayout::Coset);
+    let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    let size: usize = input_data.len() * fri_config.blow_up_factor;
+    let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data, size);
+    let first = prove_fri(LABEL, fri_config, code_word.clone());
+    let second = prove_fri(LABEL, fri_config, code_word.clone());
+
+    assert_input_not_opened(fri_config, &code_word, &first);
+    assert_input_not_opened(fri_config, &code_word, &second);
+    //fresh salts and mask: different roots, so different challenges, queries and openings
+    for (first_commitment, second_commitment) in first
+        .layer_commitments
+        .iter()
+        .zip(second.layer_commitments.iter())
+    {
+        assert_ne!(first_commitment, second_commitment);
+    }
+    for (first_opening, second_opening) in first.layer_openings.iter().zip(second.layer_openings.iter()) {
+        assert_ne!(first_opening.leaves, second_opening.leaves);
+    }
+    assert_ne!(first.final_poly, second.final_poly);
+    assert_eq!(verify_fri(LABEL, fri_config, size.ilog2(), first), Ok(()));
+    assert_eq!(verify_fri(LABEL, fri_config, size.ilog2(), second), Ok(()));
+}
+
+#[test]
+fn salt_is_authenticated() {
+    let fri_config = zk_config(2, LeafLayout::Single);
+    let input_data: Vec<Fr> = generate_random_vector::<Fr>(1 << 10);
+    let size: usize = input_data.len() * fri_config.blow_up_factor;
+    let code_word: Vec<Fr> = coeff_to_eval_blowup::<Fr>(input_data, size);
+    let friproof = prove_fri(LABEL, fri_config, code_word);
+
+    let mut tampered = friproof.clone();
+    let last = tampered.layer_openings[1].leaves[0].len() - 1;
+    tampered.layer_openings[1].leaves[0][last] ^= 1;
+    assert_eq!(
+        verify_fri(LABEL, fri_config, size.ilog2(), tampered),
+        Err(FriError::MerklePath {
+            layer: 1,
+            leaf: friproof.layer_openings[1].leaf_indices[0]
+        })
+    );
+
+    //zk is part of the config, a verifier without it reads different leaf sizes and challenges
+    let plain_config = Friconfig {
+        zk: false,
+        ..fri_config
+    };
+    assert!(verify_fri(LABEL, plain_config, size.ilog2(), friproof).is_err());
+}